@@ -5,14 +5,30 @@ pub mod database {
 
 #[path = "./entities"]
 pub mod entities {
+    pub mod audit_log;
     pub mod friendship;
     pub mod notification;
     pub mod user;
 }
 
+#[path = "./events"]
+pub mod events {
+    pub mod event_hub;
+}
+
+#[path = "./middleware"]
+pub mod middleware {
+    pub mod compression;
+    pub mod ip_rate_limit;
+    pub mod security_headers;
+}
+
 #[path = "./models"]
 pub mod models {
+    pub mod body_models;
     pub mod enums;
+    pub mod event_models;
+    pub mod feature_flags;
     pub mod notification_models;
     pub mod query_models;
     pub mod response_models;
@@ -22,10 +38,16 @@ pub mod models {
 
 #[path = "./resources"]
 pub mod resources {
+    pub mod admin;
+    pub mod auth;
+    pub mod events;
     pub mod friend;
+    pub mod limits;
     pub mod metrics;
     pub mod notification;
     pub mod ping;
+    pub mod presence;
+    pub mod time;
     pub mod timezone;
     pub mod user;
     pub mod users;
@@ -33,13 +55,30 @@ pub mod resources {
 
 #[path = "./security"]
 pub mod security {
+    pub mod access;
     pub mod authentication;
 }
 
 #[path = "./utils"]
 pub mod utils {
+    pub mod access_buffer;
+    pub mod cache;
+    pub mod fields;
+    pub mod json_body;
+    pub mod limits;
     pub mod macros;
+    pub mod ndjson;
     pub mod sanitize;
     pub mod serde_tz;
     pub mod time_operations;
 }
+
+#[path = "./workers"]
+pub mod workers {
+    pub mod access_flusher;
+    pub mod account_deleter;
+    pub mod inactive_sweeper;
+    pub mod rate_limit_pruner;
+    pub mod telemetry;
+    pub mod worker;
+}
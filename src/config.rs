@@ -0,0 +1,245 @@
+use crate::api::models::response_models::PermissionLevelLimits;
+use axum::http::{HeaderName, HeaderValue};
+use std::{env, fmt::Display, str::FromStr};
+
+/// Allows the inline scripts and CDN assets the Swagger UI, Redoc and RapiDoc pages rely on
+const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+    script-src 'self' 'unsafe-inline' https://unpkg.com https://cdn.redoc.ly; \
+    style-src 'self' 'unsafe-inline' https://fonts.googleapis.com; \
+    font-src 'self' https://fonts.gstatic.com; \
+    img-src 'self' data: https:; \
+    worker-src 'self' blob:; \
+    frame-ancestors 'none'";
+
+/// Server configuration read from environment variables, every value except the database URL has a default
+pub struct Config {
+    /// DB_URL: The MongoDB connection string
+    pub db_url: String,
+    /// API_PREFIX: The version prefix all routes are additionally mounted under
+    pub api_prefix: String,
+    /// COMPRESSION_ENABLED: If large JSON responses are gzip compressed
+    pub compression_enabled: bool,
+    /// CONTENT_SECURITY_POLICY: The CSP header sent with every response
+    pub content_security_policy: String,
+    /// DEFAULT_PAGE_SIZE: The amount of results per page if none is specified
+    pub default_page_size: u32,
+    /// MAX_PAGE_SIZE: The maximum amount of results per page
+    pub max_page_size: u32,
+    /// MAX_PENDING_FRIEND_REQUESTS_{USER,MODERATOR,ADMINISTRATOR}
+    pub max_pending_friend_requests: PermissionLevelLimits,
+    /// MAX_CONNECTIONS_{USER,MODERATOR,ADMINISTRATOR}: Simultaneous event stream connections
+    pub max_connections: PermissionLevelLimits,
+    /// ONLINE_THRESHOLD_SECONDS: Users who sent a request within this amount of seconds are considered online
+    pub online_threshold_seconds: u64,
+    /// INACTIVE_ACCOUNT_DAYS: Accounts which haven't sent a request for this amount of days are considered inactive
+    pub inactive_account_days: u64,
+    /// INACTIVE_SWEEP_DRY_RUN: Only if this is false, inactive accounts are flagged instead of just logged
+    pub inactive_sweep_dry_run: bool,
+    /// TELEMETRY_ENABLED: If anonymized usage metrics are logged periodically
+    pub telemetry_enabled: bool,
+    /// TELEMETRY_INTERVAL_HOURS: How often a telemetry report is logged
+    pub telemetry_interval_hours: u64,
+    /// ACCESS_FLUSH_INTERVAL_SECONDS: How often buffered user accesses are written to the database
+    pub access_flush_interval_seconds: u64,
+    /// STRICT_USERNAMES: If usernames with invalid characters are rejected instead of having them stripped
+    pub strict_usernames: bool,
+    /// COHORT_WINDOW_DAYS: Friends who joined within this amount of days of you count as joining around the same time
+    pub cohort_window_days: u64,
+    /// MAX_FRIENDSHIPS_PER_WINDOW: How many friend requests a user can accept within the friendship window, administrators are exempt
+    pub max_friendships_per_window: u64,
+    /// FRIENDSHIP_WINDOW_HOURS: The time window the friendship limit applies to
+    pub friendship_window_hours: u64,
+    /// QUEUE_PRIVATE_FRIEND_REQUESTS: If requests to users who don't allow friend requests are silently kept until they allow them again, instead of being rejected
+    pub queue_private_friend_requests: bool,
+    /// IP_RATE_LIMIT_REQUESTS: How many requests a single IP address can send to the endpoints which don't require an API key within the rate limit window
+    pub ip_rate_limit_requests: u32,
+    /// IP_RATE_LIMIT_WINDOW_SECONDS: The time window the IP rate limit applies to
+    pub ip_rate_limit_window_seconds: u64,
+    /// TRUSTED_PROXY_HEADER: The header a reverse proxy puts the client address in (e.g. X-Forwarded-For), only set this if every request passes through that proxy.
+    /// If unset, the address of the connecting peer is used and such headers are ignored, since clients could spoof them.
+    pub trusted_proxy_header: Option<HeaderName>,
+    /// ACCOUNT_DELETION_GRACE_DAYS: How many days after requesting it an account is permanently deleted, it can be restored until then
+    pub account_deletion_grace_days: u64,
+    /// SETTINGS_SAVE_COOLDOWN_SECONDS: The minimum amount of seconds between two changes of a user's settings
+    pub settings_save_cooldown_seconds: u64,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Config, String> {
+        let config = Config {
+            db_url: env::var("DB_URL").map_err(|_| "DB_URL is not set".to_string())?,
+            api_prefix: normalize_prefix(&env_or("API_PREFIX", "/v1".to_string())?),
+            compression_enabled: env_or("COMPRESSION_ENABLED", false)?,
+            content_security_policy: env_or(
+                "CONTENT_SECURITY_POLICY",
+                DEFAULT_CONTENT_SECURITY_POLICY.to_string(),
+            )?,
+            default_page_size: env_or("DEFAULT_PAGE_SIZE", 10)?,
+            max_page_size: env_or("MAX_PAGE_SIZE", 100)?,
+            max_pending_friend_requests: PermissionLevelLimits {
+                user: env_or("MAX_PENDING_FRIEND_REQUESTS_USER", 500)?,
+                moderator: env_or("MAX_PENDING_FRIEND_REQUESTS_MODERATOR", 1000)?,
+                administrator: env_or("MAX_PENDING_FRIEND_REQUESTS_ADMINISTRATOR", 2000)?,
+            },
+            max_connections: PermissionLevelLimits {
+                user: env_or("MAX_CONNECTIONS_USER", 5)?,
+                moderator: env_or("MAX_CONNECTIONS_MODERATOR", 5)?,
+                administrator: env_or("MAX_CONNECTIONS_ADMINISTRATOR", 20)?,
+            },
+            online_threshold_seconds: env_or("ONLINE_THRESHOLD_SECONDS", 300)?,
+            inactive_account_days: env_or("INACTIVE_ACCOUNT_DAYS", 365)?,
+            inactive_sweep_dry_run: env_or("INACTIVE_SWEEP_DRY_RUN", true)?,
+            telemetry_enabled: env_or("TELEMETRY_ENABLED", false)?,
+            telemetry_interval_hours: env_or("TELEMETRY_INTERVAL_HOURS", 24)?,
+            access_flush_interval_seconds: env_or("ACCESS_FLUSH_INTERVAL_SECONDS", 5)?,
+            strict_usernames: env_or("STRICT_USERNAMES", false)?,
+            cohort_window_days: env_or("COHORT_WINDOW_DAYS", 30)?,
+            max_friendships_per_window: env_or("MAX_FRIENDSHIPS_PER_WINDOW", 100)?,
+            friendship_window_hours: env_or("FRIENDSHIP_WINDOW_HOURS", 24)?,
+            queue_private_friend_requests: env_or("QUEUE_PRIVATE_FRIEND_REQUESTS", false)?,
+            ip_rate_limit_requests: env_or("IP_RATE_LIMIT_REQUESTS", 60)?,
+            ip_rate_limit_window_seconds: env_or("IP_RATE_LIMIT_WINDOW_SECONDS", 60)?,
+            trusted_proxy_header: match env::var("TRUSTED_PROXY_HEADER") {
+                Ok(name) if !name.trim().is_empty() => {
+                    Some(HeaderName::from_str(name.trim()).map_err(|e| {
+                        format!("TRUSTED_PROXY_HEADER is not a valid header name: {e}")
+                    })?)
+                }
+                _ => None,
+            },
+            account_deletion_grace_days: env_or("ACCOUNT_DELETION_GRACE_DAYS", 14)?,
+            settings_save_cooldown_seconds: env_or("SETTINGS_SAVE_COOLDOWN_SECONDS", 3)?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.max_page_size == 0 {
+            return Err("MAX_PAGE_SIZE has to be at least 1".to_string());
+        }
+        if self.default_page_size == 0 || self.default_page_size > self.max_page_size {
+            return Err("DEFAULT_PAGE_SIZE has to be between 1 and MAX_PAGE_SIZE".to_string());
+        }
+        if self.telemetry_interval_hours == 0 {
+            return Err("TELEMETRY_INTERVAL_HOURS has to be at least 1".to_string());
+        }
+        if self.access_flush_interval_seconds == 0 {
+            return Err("ACCESS_FLUSH_INTERVAL_SECONDS has to be at least 1".to_string());
+        }
+        if self.ip_rate_limit_window_seconds == 0 {
+            return Err("IP_RATE_LIMIT_WINDOW_SECONDS has to be at least 1".to_string());
+        }
+        if HeaderValue::from_str(&self.content_security_policy).is_err() {
+            return Err("CONTENT_SECURITY_POLICY is not a valid header value".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn online_threshold_nanos(&self) -> u64 {
+        self.online_threshold_seconds * 1_000_000_000
+    }
+
+    pub fn cohort_window_nanos(&self) -> u64 {
+        self.cohort_window_days * 86_400_000_000_000
+    }
+
+    pub fn friendship_window_nanos(&self) -> u64 {
+        self.friendship_window_hours * 3_600_000_000_000
+    }
+
+    pub fn settings_save_cooldown_nanos(&self) -> u64 {
+        self.settings_save_cooldown_seconds * 1_000_000_000
+    }
+
+    pub fn account_deletion_grace_nanos(&self) -> u64 {
+        self.account_deletion_grace_days * 86_400_000_000_000
+    }
+}
+
+/// Reads and parses an environment variable, falling back to the default if it is unset and failing if it is invalid
+fn env_or<T: FromStr>(name: &str, default: T) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|e| format!("{name} has an invalid value '{value}': {e}")),
+        Err(_) => Ok(default),
+    }
+}
+
+fn normalize_prefix(prefix: &str) -> String {
+    format!("/{}", prefix.trim_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_config() -> Config {
+        env::set_var("DB_URL", "mongodb://localhost");
+        Config::from_env().unwrap()
+    }
+
+    #[test]
+    fn env_or_falls_back_to_default() {
+        assert_eq!(env_or("LEMCOM_TEST_UNSET", 7_u64), Ok(7));
+    }
+
+    #[test]
+    fn env_or_parses_value() {
+        env::set_var("LEMCOM_TEST_VALID", "42");
+        assert_eq!(env_or("LEMCOM_TEST_VALID", 7_u64), Ok(42));
+    }
+
+    #[test]
+    fn env_or_rejects_invalid_value() {
+        env::set_var("LEMCOM_TEST_INVALID", "many");
+        let error = env_or("LEMCOM_TEST_INVALID", 7_u64).unwrap_err();
+        assert!(error.starts_with("LEMCOM_TEST_INVALID has an invalid value 'many'"));
+    }
+
+    #[test]
+    fn validate_accepts_defaults() {
+        assert!(default_config().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_page_sizes() {
+        let mut config = default_config();
+        config.max_page_size = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = default_config();
+        config.default_page_size = config.max_page_size + 1;
+        assert!(config.validate().is_err());
+
+        let mut config = default_config();
+        config.default_page_size = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_zero_intervals() {
+        let mut config = default_config();
+        config.telemetry_interval_hours = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = default_config();
+        config.access_flush_interval_seconds = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = default_config();
+        config.ip_rate_limit_window_seconds = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_invalid_content_security_policy() {
+        let mut config = default_config();
+        config.content_security_policy = "default-src\n'self'".to_string();
+        assert!(config.validate().is_err());
+    }
+}
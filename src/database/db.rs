@@ -1,27 +1,151 @@
-use crate::api::entities::{friendship::Friendship, notification::Notification, user::User};
-use dotenvy::dotenv;
-use mongodb::{error::Result, options::ClientOptions, Client, Collection};
-use std::env;
+use crate::api::entities::{
+    audit_log::AuditLogEntry,
+    friendship::Friendship,
+    notification::Notification,
+    user::{backfill_normalized_names, User},
+};
+use crate::config::Config;
+use mongodb::{
+    bson::doc,
+    error::{Error, ErrorKind, Result, WriteFailure},
+    options::{ClientOptions, IndexOptions},
+    Client, Collection, IndexModel,
+};
 
 #[derive(Clone)]
 pub struct DB {
+    #[allow(dead_code)]
     pub client: Client,
     pub user_collection: Collection<User>,
     pub friendship_collection: Collection<Friendship>,
     pub notification_collection: Collection<Notification>,
+    pub audit_log_collection: Collection<AuditLogEntry>,
 }
 
-pub async fn setup() -> Result<DB> {
-    dotenv().expect("Failed to load .env");
-    let mongo_url = env::var("DB_URL").expect("DB URL not set.");
-    let client_options = ClientOptions::parse(mongo_url).await?;
+pub async fn setup(config: &Config) -> Result<DB> {
+    let client_options = ClientOptions::parse(&config.db_url).await?;
     let client = Client::with_options(client_options)?;
     let db = client.database("LemCom");
 
-    Ok(DB {
+    let database = DB {
         client,
         user_collection: db.collection("users"),
         friendship_collection: db.collection("friendships"),
         notification_collection: db.collection("notifications"),
-    })
+        audit_log_collection: db.collection("audit_log"),
+    };
+    create_indexes(&database).await?;
+
+    let backfilled = backfill_normalized_names(&database.user_collection).await?;
+    if backfilled > 0 {
+        println!("Filled in the normalized display name of {backfilled} user(s)");
+    }
+
+    Ok(database)
+}
+
+async fn create_indexes(database: &DB) -> Result<()> {
+    for field in ["key", "name"] {
+        let index = IndexModel::builder()
+            .keys(doc! { field: 1 })
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        database.user_collection.create_index(index, None).await?;
+    }
+
+    // Used for sorting by join date and computing join ranks
+    let created_index = IndexModel::builder()
+        .keys(doc! { "created_stamp": 1 })
+        .build();
+    database
+        .user_collection
+        .create_index(created_index, None)
+        .await?;
+
+    // Used for looking up users by their display name
+    let name_normalized_index = IndexModel::builder()
+        .keys(doc! { "name_normalized": 1 })
+        .build();
+    database
+        .user_collection
+        .create_index(name_normalized_index, None)
+        .await?;
+
+    // Block lists are keyed by the blocked user, a wildcard index allows looking up who blocked someone
+    let block_list_index = IndexModel::builder()
+        .keys(doc! { "block_list.$**": 1 })
+        .build();
+    database
+        .user_collection
+        .create_index(block_list_index, None)
+        .await?;
+
+    // Friend requests are keyed by the sender, this allows counting the requests someone sent
+    let friend_requests_index = IndexModel::builder()
+        .keys(doc! { "friend_requests.$**": 1 })
+        .build();
+    database
+        .user_collection
+        .create_index(friend_requests_index, None)
+        .await?;
+
+    let pair_index = IndexModel::builder()
+        .keys(doc! { "pair_key": 1 })
+        .options(
+            IndexOptions::builder()
+                .unique(true)
+                .partial_filter_expression(doc! { "pair_key": { "$exists": true } })
+                .build(),
+        )
+        .build();
+    database
+        .friendship_collection
+        .create_index(pair_index, None)
+        .await?;
+
+    Ok(())
+}
+
+/// Checks if the error was caused by violating a unique index, e.g. when two requests race to insert the same document
+pub fn is_duplicate_key_error(error: &Error) -> bool {
+    const DUPLICATE_KEY_CODE: i32 = 11000;
+    match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => {
+            write_error.code == DUPLICATE_KEY_CODE
+        }
+        ErrorKind::Command(command_error) => command_error.code == DUPLICATE_KEY_CODE,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::{
+        bson,
+        error::{CommandError, WriteError},
+    };
+
+    fn write_error(code: i32) -> Error {
+        let write_error: WriteError = bson::from_document(doc! { "code": code }).unwrap();
+        Error::from(ErrorKind::Write(WriteFailure::WriteError(write_error)))
+    }
+
+    fn command_error(code: i32) -> Error {
+        let command_error: CommandError = bson::from_document(doc! { "code": code }).unwrap();
+        Error::from(ErrorKind::Command(command_error))
+    }
+
+    #[test]
+    fn detects_duplicate_key_errors() {
+        assert!(is_duplicate_key_error(&write_error(11000)));
+        assert!(is_duplicate_key_error(&command_error(11000)));
+    }
+
+    #[test]
+    fn ignores_other_errors() {
+        assert!(!is_duplicate_key_error(&write_error(121)));
+        assert!(!is_duplicate_key_error(&command_error(13)));
+        assert!(!is_duplicate_key_error(&Error::custom("unrelated")));
+    }
 }
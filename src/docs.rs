@@ -1,5 +1,5 @@
-use utoipa::{openapi::security::{ApiKey, ApiKeyValue, SecurityScheme}, Modify, OpenApi};
-use crate::api::{self, models::{enums::{PermissionLevel, PrivacyLevel}, notification_models::{FriendRequestNotification, NotificationList, NotificationResponse, TestNotification}, response_models::{BlockList, BlockListEntry, CountResponse, FriendInformation, FriendList, FriendRequestInformation, FriendRequests, MessageResponse, Pagination, UserList, UserPrivateInformation, UserPublicInformation}, user_profile::UserProfile, user_settings::UserSettings}};
+use utoipa::{openapi::{security::{ApiKey, ApiKeyValue, SecurityScheme}, server::Server}, Modify, OpenApi};
+use crate::api::{self, models::{body_models::{BlockImport, FriendRequestBatch, PublicKeyUpload}, enums::{Capability, PermissionLevel, PrivacyLevel, SortOrder}, event_models::{FriendRequestAcceptedEvent, FriendRequestReceivedEvent, ServerEvent}, notification_models::{FriendRequestNotification, NotificationList, NotificationResponse, TestNotification}, response_models::{AccountDeletion, ServerTime, TimeFormat, AllowedEndpoints, EndpointInformation, RetentionStatus, AuthCheck, BlockImportResult, BlockList, FriendGraph, FriendGraphEdge, BlockedByEntry, BlockedByList, SessionInformation, SessionList, BlockListEntry, CountResponse, FriendCount, PermissionLevelCounts, PresencePing, FriendInformation, FriendList, FriendPresence, FriendPresenceList, FriendRequestInformation, FriendRequests, FriendRequestSummary, FriendRequestBatchOutcome, FriendRequestBatchResult, FriendshipExistence, MessageResponse, Pagination, PermissionLevelLimits, ServerCapabilities, ServerLimits, SettingDescriptor, UserList, UserAccountSummary, JoinRank, PublicKeyInformation, UserPermissions, UserPrivateInformation, UserPublicInformation, UserRelationship, UserSettingsInformation, UserSettingsSchema, UserSupportSnapshot}, user_profile::UserProfile, user_settings::{DoNotDisturbSchedule, NotificationSettings, UserSettings}}};
 
 #[derive(OpenApi)]
 #[openapi(
@@ -8,30 +8,55 @@ use crate::api::{self, models::{enums::{PermissionLevel, PrivacyLevel}, notifica
         description="A webservice for handling LemCom online services. LemCom will be a messaging application for desktop written in Rust.\n\nAll available docs: Rapidoc (/docs), Swagger (/swagger) and Redoc (/redoc).\n\nIf you find bugs or have feedback please create an issue here: https://github.com/Zitronenjoghurt/lemcom-api/issues"
     ),
     paths(
+        api::resources::admin::delete_admin_friend,
+        api::resources::admin::get_admin_friend_exists,
+        api::resources::admin::get_admin_impersonate,
+        api::resources::admin::post_admin_maintenance_cleanup_requests,
+        api::resources::admin::get_admin_stats_inactive, api::resources::admin::get_admin_stats_permissions,
+        api::resources::admin::get_admin_user_blocked_by,
+        api::resources::admin::get_admin_user_by_key,
+        api::resources::admin::patch_admin_user_flags,
+        api::resources::auth::get_auth_capabilities, api::resources::auth::get_auth_check,
+        api::resources::events::get_events,
         api::resources::friend::get_friend,
         api::resources::friend::delete_friend,
+        api::resources::friend::get_friend_cohort, api::resources::friend::get_friend_count, api::resources::friend::get_friend_detailed, api::resources::friend::post_friend_favorite, api::resources::friend::delete_friend_favorite,
         api::resources::friend::delete_friend_request,
         api::resources::friend::get_friend_request,
         api::resources::friend::post_friend_request,
-        api::resources::friend::post_friend_request_accept,
-        api::resources::friend::post_friend_request_deny,
+        api::resources::friend::post_friend_request_accept, api::resources::friend::post_friend_request_accept_bulk,
+        api::resources::friend::post_friend_request_deny, api::resources::friend::post_friend_request_deny_bulk, api::resources::friend::get_friend_request_summary,
+        api::resources::friend::get_friend_suggestions,
+        api::resources::limits::get_capabilities,
+        api::resources::limits::get_limits,
         api::resources::metrics::get_metrics_usage,
         api::resources::notification::get_notification,
         api::resources::notification::delete_notification,
-        api::resources::ping::get_ping,  
+        api::resources::ping::get_ping, api::resources::presence::post_presence_ping, api::resources::time::get_time,  
         api::resources::timezone::get_timezone,
         api::resources::timezone::put_timezone,
-        api::resources::user::get_user,
+        api::resources::user::get_user, api::resources::user::delete_user, api::resources::user::post_user_undelete, api::resources::user::get_user_retention, api::resources::user::get_user_me,
+        api::resources::user::get_user_flags,
+        api::resources::user::get_user_permission,
         api::resources::user::post_user_block,
         api::resources::user::get_user_block,
         api::resources::user::delete_user_block,
+        api::resources::user::post_user_block_import,
+        api::resources::user::get_user_graph_export,
+        api::resources::user::get_user_pubkey, api::resources::user::put_user_pubkey, api::resources::user::get_user_rank, api::resources::user::get_user_relationship,
         api::resources::user::get_user_search,
+        api::resources::user::get_user_sessions,
+        api::resources::user::delete_user_session,
         api::resources::user::get_user_settings,
+        api::resources::user::get_user_settings_schema,
+        api::resources::user::post_user_settings_reset,
         api::resources::user::patch_user_profile,
         api::resources::user::patch_user_settings,
-        api::resources::users::get_users
+        api::resources::users::get_users,
+        api::resources::users::get_users_recent
     ),
     tags(
+        (name = "Admin", description = "Endpoints for administrators"),
         (name = "Misc", description = "Miscellaneous endpoints"),
         (name = "Notification", description = "Endpoints for handling your notifications"),
         (name = "User", description = "User management endpoints"),
@@ -40,7 +65,7 @@ use crate::api::{self, models::{enums::{PermissionLevel, PrivacyLevel}, notifica
     ),
     modifiers(&SecurityAddon),
     components(
-        schemas(MessageResponse, UserPublicInformation, UserPrivateInformation, UserSettings, UserList, Pagination, PrivacyLevel, PermissionLevel, FriendRequestInformation, FriendRequests, FriendInformation, FriendList, UserProfile, BlockList, BlockListEntry, NotificationList, NotificationResponse, FriendRequestNotification, TestNotification, CountResponse),
+        schemas(MessageResponse, AccountDeletion, ServerTime, TimeFormat, AllowedEndpoints, EndpointInformation, RetentionStatus, AuthCheck, UserPublicInformation, UserPrivateInformation, UserSettings, NotificationSettings, DoNotDisturbSchedule, UserSettingsInformation, UserSettingsSchema, UserSupportSnapshot, SettingDescriptor, UserList, Pagination, PrivacyLevel, PermissionLevel, Capability, SortOrder, UserAccountSummary, JoinRank, PublicKeyInformation, UserPermissions, UserRelationship, FriendRequestInformation, FriendRequests, FriendRequestSummary, FriendRequestBatchOutcome, FriendRequestBatchResult, FriendshipExistence, FriendCount, PermissionLevelCounts, PresencePing, FriendInformation, FriendList, FriendPresence, FriendPresenceList, UserProfile, BlockList, BlockListEntry, FriendGraph, FriendGraphEdge, BlockedByEntry, BlockedByList, BlockImport, FriendRequestBatch, PublicKeyUpload, BlockImportResult, SessionInformation, SessionList, NotificationList, NotificationResponse, FriendRequestNotification, TestNotification, CountResponse, ServerCapabilities, ServerLimits, PermissionLevelLimits, ServerEvent, FriendRequestReceivedEvent, FriendRequestAcceptedEvent),
     )
 )]
 pub struct ApiDoc;
@@ -56,4 +81,154 @@ impl Modify for SecurityAddon {
             )
         }
     }
-}
\ No newline at end of file
+}
+
+impl ApiDoc {
+    /// The documentation listing the prefixed routes as well as the unprefixed ones
+    pub fn with_servers(api_prefix: &str) -> utoipa::openapi::OpenApi {
+        let mut openapi = ApiDoc::openapi();
+        let mut servers = vec![Server::new("/")];
+        if api_prefix != "/" {
+            servers.insert(0, Server::new(api_prefix));
+        }
+        openapi.servers = Some(servers);
+        openapi
+    }
+}
+
+/// The name and source of every resource module, tests read the routes and handlers of the API from them
+#[cfg(test)]
+pub const RESOURCE_SOURCES: [(&str, &str); 13] = [
+    ("admin", include_str!("resources/admin.rs")),
+    ("auth", include_str!("resources/auth.rs")),
+    ("events", include_str!("resources/events.rs")),
+    ("friend", include_str!("resources/friend.rs")),
+    ("limits", include_str!("resources/limits.rs")),
+    ("metrics", include_str!("resources/metrics.rs")),
+    ("notification", include_str!("resources/notification.rs")),
+    ("ping", include_str!("resources/ping.rs")),
+    ("presence", include_str!("resources/presence.rs")),
+    ("time", include_str!("resources/time.rs")),
+    ("timezone", include_str!("resources/timezone.rs")),
+    ("user", include_str!("resources/user.rs")),
+    ("users", include_str!("resources/users.rs")),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::security::access::method_name;
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    const SCHEMA_REF_PREFIX: &str = "#/components/schemas/";
+
+    fn collect_schema_refs(value: &Value, refs: &mut BTreeSet<String>) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    match value {
+                        Value::String(reference) if key == "$ref" => {
+                            if let Some(name) = reference.strip_prefix(SCHEMA_REF_PREFIX) {
+                                refs.insert(name.to_string());
+                            }
+                        }
+                        _ => collect_schema_refs(value, refs),
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    collect_schema_refs(value, refs);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn every_referenced_schema_is_registered() {
+        let openapi = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = openapi["components"]["schemas"].as_object().unwrap();
+
+        let mut refs = BTreeSet::new();
+        collect_schema_refs(&openapi, &mut refs);
+        let missing: Vec<&String> = refs
+            .iter()
+            .filter(|name| !schemas.contains_key(*name))
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "Schemas referenced but not registered in ApiDoc: {missing:?}"
+        );
+    }
+
+    /// The method and path of every route the resource routers register, with axum's path parameters written like in the documentation
+    fn routed_endpoints() -> BTreeSet<(String, String)> {
+        let mut endpoints = BTreeSet::new();
+        for (_, source) in RESOURCE_SOURCES {
+            for route in source.split(".route(").skip(1) {
+                let mut parts = route.splitn(3, '"');
+                let path = parts.nth(1).unwrap();
+                let method = parts
+                    .next()
+                    .unwrap()
+                    .trim_start_matches(',')
+                    .trim_start()
+                    .split('(')
+                    .next()
+                    .unwrap();
+                let path = path
+                    .split('/')
+                    .map(|segment| match segment.strip_prefix(':') {
+                        Some(parameter) => format!("{{{parameter}}}"),
+                        None => segment.to_string(),
+                    })
+                    .collect::<Vec<String>>()
+                    .join("/");
+                endpoints.insert((method.to_uppercase(), path));
+            }
+        }
+        endpoints
+    }
+
+    fn documented_endpoints() -> BTreeSet<(String, String)> {
+        let mut endpoints = BTreeSet::new();
+        for (path, item) in ApiDoc::openapi().paths.paths {
+            for method in item.operations.keys() {
+                endpoints.insert((method_name(method).to_string(), path.clone()));
+            }
+        }
+        endpoints
+    }
+
+    #[test]
+    fn every_resource_router_is_nested() {
+        let nested: BTreeSet<&str> = include_str!("main.rs")
+            .split("resources::")
+            .skip(1)
+            .filter_map(|rest| rest.split_once("::"))
+            .filter(|(_, rest)| rest.starts_with("router()"))
+            .map(|(name, _)| name)
+            .collect();
+        let resources: BTreeSet<&str> = RESOURCE_SOURCES.iter().map(|(name, _)| *name).collect();
+        assert_eq!(nested, resources);
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let routed = routed_endpoints();
+        let documented = documented_endpoints();
+
+        let undocumented: Vec<_> = routed.difference(&documented).collect();
+        assert!(
+            undocumented.is_empty(),
+            "Routes without a documented utoipa::path: {undocumented:?}"
+        );
+        let unrouted: Vec<_> = documented.difference(&routed).collect();
+        assert!(
+            unrouted.is_empty(),
+            "Documented paths without a route: {unrouted:?}"
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use crate::api::utils::time_operations::timestamp_now_nanos;
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+
+/// Sensitive actions which are recorded in the audit log
+#[derive(Serialize, Deserialize)]
+pub enum AuditAction {
+    FriendshipRemoved,
+    UserLookedUpByKey,
+    FeatureFlagChanged,
+    UserImpersonated,
+    BlockedByLookedUp,
+    OrphanedFriendRequestsRemoved,
+    FriendshipLookedUp,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub action: AuditAction,
+    pub actor_key: String,
+    pub target_keys: Vec<String>,
+    /// How many records the action affected, for actions which aren't limited to the target users
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub affected_count: Option<u64>,
+    pub created_stamp: u64,
+}
+
+impl AuditLogEntry {
+    pub fn new(action: AuditAction, actor_key: &str, target_keys: Vec<String>) -> AuditLogEntry {
+        AuditLogEntry {
+            id: None,
+            action,
+            actor_key: actor_key.to_string(),
+            target_keys,
+            affected_count: None,
+            created_stamp: timestamp_now_nanos(),
+        }
+    }
+
+    pub async fn record(
+        collection: &Collection<AuditLogEntry>,
+        action: AuditAction,
+        actor_key: &str,
+        target_keys: Vec<String>,
+    ) -> mongodb::error::Result<ObjectId> {
+        let entry = AuditLogEntry::new(action, actor_key, target_keys);
+        let result = collection.insert_one(entry, None).await?;
+        Ok(result
+            .inserted_id
+            .as_object_id()
+            .expect("Audit log entries have generated object ids"))
+    }
+
+    /// Completes a recorded entry once the action finished and its outcome is known
+    pub async fn set_affected_count(
+        collection: &Collection<AuditLogEntry>,
+        id: &ObjectId,
+        count: u64,
+    ) -> mongodb::error::Result<()> {
+        let update = doc! { "$set": { "affected_count": count as i64 } };
+        collection
+            .update_one(doc! { "_id": id }, update, None)
+            .await?;
+        Ok(())
+    }
+}
@@ -2,24 +2,32 @@ use crate::api::utils::time_operations::timestamp_now_nanos;
 use futures::TryStreamExt;
 use mongodb::{
     bson::{self, doc, oid::ObjectId},
-    options::{InsertOneOptions, UpdateOptions},
+    options::{FindOptions, InsertOneOptions, UpdateOptions},
     Collection,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Serialize, Deserialize)]
 pub struct Friendship {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub keys: Vec<String>,
+    /// The sorted keys joined together, unique across all friendships to prevent duplicates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pair_key: Option<String>,
     pub created_stamp: u64,
 }
 
 impl Friendship {
     pub fn new(keys: Vec<String>) -> Friendship {
+        let mut sorted_keys = keys.clone();
+        sorted_keys.sort();
+
         Friendship {
             id: None,
             keys,
+            pair_key: Some(sorted_keys.join(":")),
             created_stamp: timestamp_now_nanos(),
         }
     }
@@ -57,6 +65,85 @@ pub async fn find_friendships_by_key(
     Ok(friendships)
 }
 
+/// Finds up to `limit` friendships which involve any of the given users
+pub async fn find_friendships_of_keys(
+    collection: &Collection<Friendship>,
+    keys: &[String],
+    limit: i64,
+) -> mongodb::error::Result<Vec<Friendship>> {
+    let filter = doc! { "keys": { "$in": keys } };
+    let find_options = FindOptions::builder().limit(limit).build();
+    let cursor = collection.find(filter, find_options).await?;
+    cursor.try_collect().await
+}
+
+pub async fn find_friend_keys(
+    collection: &Collection<Friendship>,
+    key: &str,
+) -> mongodb::error::Result<HashSet<String>> {
+    let friendships = find_friendships_by_key(collection, key).await?;
+    let friend_keys = friendships
+        .into_iter()
+        .flat_map(|friendship| friendship.keys)
+        .filter(|k| k != key)
+        .collect();
+    Ok(friend_keys)
+}
+
+pub async fn count_mutual_friends(
+    collection: &Collection<Friendship>,
+    key: &str,
+    other_key: &str,
+) -> mongodb::error::Result<u64> {
+    let friends = find_friend_keys(collection, key).await?;
+    let other_friends = find_friend_keys(collection, other_key).await?;
+    Ok(friends.intersection(&other_friends).count() as u64)
+}
+
+/// Counts how often every user who isn't yet a friend appears among the friendships of your friends
+pub async fn count_friends_of_friends(
+    collection: &Collection<Friendship>,
+    key: &str,
+    friend_keys: &HashSet<String>,
+    fan_out: i64,
+) -> mongodb::error::Result<HashMap<String, u64>> {
+    let friend_keys: Vec<&String> = friend_keys.iter().collect();
+    let mut excluded_keys = friend_keys.clone();
+    let own_key = key.to_string();
+    excluded_keys.push(&own_key);
+
+    let pipeline = vec![
+        doc! { "$match": { "keys": { "$in": &friend_keys } } },
+        doc! { "$limit": fan_out },
+        doc! { "$unwind": "$keys" },
+        doc! { "$match": { "keys": { "$nin": &excluded_keys } } },
+        doc! { "$group": { "_id": "$keys", "count": { "$sum": 1 } } },
+    ];
+
+    let mut cursor = collection.aggregate(pipeline, None).await?;
+    let mut counts = HashMap::new();
+    while let Some(entry) = cursor.try_next().await? {
+        if let (Ok(key), Ok(count)) = (entry.get_str("_id"), entry.get_i32("count")) {
+            counts.insert(key.to_string(), count as u64);
+        }
+    }
+    Ok(counts)
+}
+
+/// Counts the friendships of a user which were created at or after the given time
+pub async fn count_friendships_since(
+    collection: &Collection<Friendship>,
+    key: &str,
+    since_stamp: u64,
+) -> mongodb::error::Result<u64> {
+    let filter = doc! { "keys": key, "created_stamp": { "$gte": since_stamp as i64 } };
+    collection.count_documents(filter, None).await
+}
+
+pub async fn count_friendships(collection: &Collection<Friendship>) -> mongodb::error::Result<u64> {
+    collection.count_documents(doc! {}, None).await
+}
+
 pub async fn remove_friendship_by_id(
     collection: &Collection<Friendship>,
     object_id: &ObjectId,
@@ -65,6 +152,14 @@ pub async fn remove_friendship_by_id(
     collection.delete_one(filter, None).await
 }
 
+pub async fn remove_friendships_by_key(
+    collection: &Collection<Friendship>,
+    key: &str,
+) -> mongodb::error::Result<u64> {
+    let result = collection.delete_many(doc! { "keys": key }, None).await?;
+    Ok(result.deleted_count)
+}
+
 pub async fn are_friends(
     collection: &Collection<Friendship>,
     keys: Vec<String>,
@@ -15,6 +15,7 @@ use crate::api::{
 };
 
 use super::user::{find_user_by_key, User};
+use crate::config::Config;
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -57,10 +58,13 @@ impl Notification {
         &self,
         viewer_user: &User,
         database: &DB,
+        config: &Config,
     ) -> Result<NotificationResponse, Error> {
         match self {
             Notification::FriendRequestReceived(friend_request) => {
-                friend_request.get_response(viewer_user, database).await
+                friend_request
+                    .get_response(viewer_user, database, config)
+                    .await
             }
         }
     }
@@ -72,6 +76,7 @@ pub trait IntoNotificationResponse {
         &self,
         viewer_user: &User,
         database: &DB,
+        config: &Config,
     ) -> Result<NotificationResponse, Error>;
 }
 
@@ -85,6 +90,14 @@ pub async fn find_notifications_by_receiver_key(
     Ok(notifications)
 }
 
+pub async fn count_notifications_by_receiver_key(
+    collection: &Collection<Notification>,
+    key: &str,
+) -> mongodb::error::Result<u64> {
+    let filter = doc! { "common.receiver_key": key };
+    collection.count_documents(filter, None).await
+}
+
 pub async fn clear_notifications_by_key(
     collection: &Collection<Notification>,
     key: &str,
@@ -94,6 +107,16 @@ pub async fn clear_notifications_by_key(
     Ok(result.deleted_count)
 }
 
+/// Removes the notifications the given user received or caused
+pub async fn remove_notifications_involving_key(
+    collection: &Collection<Notification>,
+    key: &str,
+) -> mongodb::error::Result<u64> {
+    let filter = doc! { "$or": [{ "common.receiver_key": key }, { "sender_key": key }] };
+    let result = collection.delete_many(filter, None).await?;
+    Ok(result.deleted_count)
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CommonFields {
     pub created_at: u64,
@@ -133,14 +156,21 @@ impl IntoNotificationResponse for FriendRequestReceived {
         &self,
         viewer_user: &User,
         database: &DB,
+        config: &Config,
     ) -> Result<NotificationResponse, Error> {
         let user = find_user_by_key(&database.user_collection, &self.sender_key).await?;
-        let user_information =
-            user.map(|u| u.public_information(false, false, &viewer_user.timezone));
-        let response = NotificationResponse::FriendRequest(FriendRequestNotification {
+        let user_information = user.map(|u| {
+            u.public_information(
+                false,
+                false,
+                &viewer_user.timezone,
+                config.online_threshold_nanos(),
+            )
+        });
+        let response = NotificationResponse::FriendRequest(Box::new(FriendRequestNotification {
             sender: user_information,
             date: nanos_to_date(self.common.created_at, &viewer_user.timezone),
-        });
+        }));
         Ok(response)
     }
 }
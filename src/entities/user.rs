@@ -1,31 +1,43 @@
 use crate::api::entities::friendship::{find_friendships_by_key, Friendship};
+use crate::api::models::feature_flags::KNOWN_FEATURE_FLAGS;
+use crate::api::models::query_models::UserSettingsEdit;
+use crate::api::models::response_models::UserSettingsInformation;
 use crate::api::models::response_models::{
-    BlockList, BlockListEntry, FriendList, FriendRequestInformation, UserPrivateInformation,
-    UserPublicInformation,
+    BlockList, BlockListEntry, FriendList, FriendPresence, FriendPresenceList,
+    FriendRequestInformation, UserPrivateInformation, UserPublicInformation,
 };
 use crate::api::models::user_profile::UserProfile;
 use crate::api::models::{
-    enums::PermissionLevel,
+    enums::{PermissionLevel, SortOrder},
     response_models::{FriendInformation, FriendRequests, Pagination},
     user_settings::UserSettings,
 };
+use crate::api::utils::access_buffer::PendingAccess;
+use crate::api::utils::sanitize::normalize_search_term;
 use crate::api::utils::serde_tz;
-use crate::api::utils::time_operations::{nanos_to_date, timestamp_now_nanos};
+use crate::api::utils::time_operations::{
+    is_within_daily_window, nanos_to_date, nanos_to_time_of_day, parse_time_of_day,
+    timestamp_now_nanos,
+};
+use axum::extract::Query;
 use chrono_tz::Tz;
-use futures::{future::try_join_all, TryStreamExt};
+use futures::{future::try_join_all, Stream, TryStreamExt};
 use mongodb::{
-    bson::{self, doc},
+    bson::{self, doc, Document},
     options::{FindOptions, UpdateOptions},
     Collection,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Serialize, Deserialize)]
 pub struct User {
     pub key: String,
     pub name: String,
     pub display_name: String,
+    /// The normalized display name used for search, updated whenever the user is saved
+    #[serde(default)]
+    pub name_normalized: String,
     pub created_stamp: u64,
     #[serde(default)]
     pub last_access_stamp: u64,
@@ -34,49 +46,211 @@ pub struct User {
     #[serde(default)]
     pub settings: UserSettings,
     #[serde(default)]
+    pub settings_changed_stamps: HashMap<String, u64>,
+    /// When the user last saved their settings, used to limit how often they can be changed
+    #[serde(default)]
+    pub last_settings_change_stamp: u64,
+    #[serde(default)]
     pub permission_level: PermissionLevel,
     #[serde(default)]
     pub friend_requests: HashMap<String, u64>,
+    /// Friend requests received while the user didn't allow any, they become regular requests once the user allows them again
+    #[serde(default)]
+    pub queued_friend_requests: HashMap<String, u64>,
     #[serde(default)]
     pub profile: UserProfile,
     #[serde(default = "default_tz", with = "serde_tz")]
     pub timezone: Tz,
     #[serde(default)]
     pub block_list: HashMap<String, u64>,
+    /// The keys of the friends the user marked as favorite
+    #[serde(default)]
+    pub favorite_keys: HashSet<String>,
+    #[serde(default)]
+    pub feature_flags: HashMap<String, bool>,
+    /// Set by the inactive account sweeper, cleared as soon as the user sends a request again
+    #[serde(default)]
+    pub inactive: bool,
+    /// An opaque public key the user published for other users, never used by the server itself
+    #[serde(default)]
+    pub public_key: Option<String>,
+    /// Set when the user requested the deletion of their account, the account is hidden and permanently deleted after this time
+    #[serde(default)]
+    pub delete_after_stamp: Option<u64>,
 }
 
 fn default_tz() -> Tz {
     "UTC".parse().unwrap()
 }
 
+/// Fields which are written by the access flusher, saving the in-memory values would overwrite accesses which were flushed in the meantime
+const ACCESS_FIELDS: [&str; 3] = ["last_access_stamp", "endpoint_usage", "inactive"];
+
+/// Which pending friend requests to list and in which order
+pub struct FriendRequestSelection {
+    pub sort: SortOrder,
+    /// Only requests sent at or after this time
+    pub since: Option<u64>,
+}
+
+/// Whether a user is currently online and when they last sent a request
+pub struct Presence {
+    pub online: bool,
+    pub last_seen_stamp: u64,
+}
+
 impl User {
+    /// Saves the user, except for the access related fields which only the access flusher writes to existing users
     pub async fn save(&self, collection: &Collection<User>) -> mongodb::error::Result<()> {
         let filter = doc! { "key": &self.key };
-        let update = doc! { "$set": bson::to_bson(self)? };
+        let update = self.save_update()?;
         let options = UpdateOptions::builder().upsert(true).build();
 
         collection.update_one(filter, update, Some(options)).await?;
         Ok(())
     }
 
-    pub fn use_endpoint(&mut self, method: &str, path: &str) {
-        self.last_access_stamp = timestamp_now_nanos();
-        *self
-            .endpoint_usage
-            .entry(format!("{method} {path}"))
-            .or_insert(0) += 1;
+    fn save_update(&self) -> bson::ser::Result<Document> {
+        let mut document = bson::to_document(self)?;
+        document.insert("name_normalized", normalize_search_term(&self.display_name));
+
+        let mut access_fields = Document::new();
+        for field in ACCESS_FIELDS {
+            if let Some(value) = document.remove(field) {
+                access_fields.insert(field, value);
+            }
+        }
+        Ok(doc! { "$set": document, "$setOnInsert": access_fields })
     }
 
+    /// Marks the user as active again, the access itself is buffered and written by the access flusher
+    pub fn mark_accessed(&mut self, stamp: u64) {
+        self.last_access_stamp = self.last_access_stamp.max(stamp);
+        self.inactive = false;
+    }
+
+    /// Checks if the user may form another friendship within the current window, administrators are exempt from the limit
+    pub fn can_form_friendship(&self, recent_friendships: u64, max_per_window: u64) -> bool {
+        self.permission_level >= PermissionLevel::Administrator
+            || recent_friendships < max_per_window
+    }
+
+    /// Checks if enough time passed since the last settings change for the settings to be changed again
+    pub fn can_change_settings(&self, cooldown_nanos: u64, now: u64) -> bool {
+        now.saturating_sub(self.last_settings_change_stamp) >= cooldown_nanos
+    }
+
+    pub fn update_settings(&mut self, data: Query<UserSettingsEdit>) {
+        let changed = self.settings.update(data);
+        let now = timestamp_now_nanos();
+        self.last_settings_change_stamp = now;
+        for name in changed {
+            self.settings_changed_stamps.insert(name.to_string(), now);
+        }
+        self.release_queued_friend_requests();
+    }
+
+    /// Restores the default settings and records which ones changed, the display name is reset to the username if requested
+    pub fn reset_settings(&mut self, reset_display_name: bool) {
+        let previous = self.settings.schema();
+        self.settings = UserSettings::default();
+
+        let now = timestamp_now_nanos();
+        self.last_settings_change_stamp = now;
+        for (old, new) in previous.iter().zip(self.settings.schema()) {
+            if old.value != new.value {
+                self.settings_changed_stamps.insert(new.name, now);
+            }
+        }
+
+        if reset_display_name {
+            self.display_name = self.name.clone();
+        }
+        self.release_queued_friend_requests();
+    }
+
+    /// Turns the queued friend requests into regular ones if the user allows friend requests, requests of blocked users are dropped
+    fn release_queued_friend_requests(&mut self) {
+        if self.settings.allow_friend_requests {
+            let block_list = &self.block_list;
+            self.friend_requests.extend(
+                self.queued_friend_requests
+                    .drain()
+                    .filter(|(key, _)| !block_list.contains_key(key)),
+            );
+        }
+    }
+
+    pub fn settings_information(&self) -> UserSettingsInformation {
+        UserSettingsInformation {
+            settings: self.settings.clone(),
+            last_changed_dates: self
+                .settings_changed_stamps
+                .iter()
+                .map(|(name, stamp)| (name.clone(), nanos_to_date(*stamp, &self.timezone)))
+                .collect(),
+        }
+    }
+
+    /// Blocks the user and drops their pending and queued friend requests
     pub fn block_user(&mut self, key: &str) -> Result<(), &'static str> {
         if self.block_list.contains_key(key) {
             Err("User already blocked")
         } else {
             self.block_list
                 .insert(key.to_string(), timestamp_now_nanos());
+            self.friend_requests.remove(key);
+            self.queued_friend_requests.remove(key);
             Ok(())
         }
     }
 
+    /// Marks a friend as favorite, only friends can become favorites
+    pub fn add_favorite(&mut self, key: &str, is_friend: bool) -> Result<(), &'static str> {
+        if !is_friend {
+            return Err("Not friends with the user");
+        }
+        if !self.favorite_keys.insert(key.to_string()) {
+            return Err("Friend is already a favorite");
+        }
+        Ok(())
+    }
+
+    /// Schedules the deletion of the account after the grace period, returns when it will be deleted
+    pub fn schedule_deletion(&mut self, now: u64, grace_nanos: u64) -> Result<u64, &'static str> {
+        if self.delete_after_stamp.is_some() {
+            return Err("Your account is already scheduled for deletion");
+        }
+        let delete_after_stamp = now + grace_nanos;
+        self.delete_after_stamp = Some(delete_after_stamp);
+        Ok(delete_after_stamp)
+    }
+
+    /// Cancels the scheduled deletion, as long as its grace period didn't end yet
+    pub fn restore(&mut self, now: u64) -> Result<(), &'static str> {
+        match self.delete_after_stamp {
+            None => Err("Your account is not scheduled for deletion"),
+            Some(stamp) if stamp <= now => Err("The grace period of your account deletion ended"),
+            Some(_) => {
+                self.delete_after_stamp = None;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn has_feature(&self, flag: &str) -> bool {
+        self.feature_flags.get(flag).copied().unwrap_or(false)
+    }
+
+    pub fn resolved_feature_flags(&self) -> HashMap<String, bool> {
+        let mut flags: HashMap<String, bool> = KNOWN_FEATURE_FLAGS
+            .iter()
+            .map(|flag| (flag.to_string(), self.has_feature(flag)))
+            .collect();
+        flags.extend(self.feature_flags.clone());
+        flags
+    }
+
     pub fn request_count(&self) -> u64 {
         self.endpoint_usage.values().sum()
     }
@@ -91,6 +265,39 @@ impl User {
             permission_level: self.permission_level.clone(),
             profile: self.profile.clone(),
             timezone: self.timezone.to_string(),
+            do_not_disturb: self.is_do_not_disturb(timestamp_now_nanos()),
+            deletion_date: self
+                .delete_after_stamp
+                .map(|stamp| nanos_to_date(stamp, &self.timezone)),
+        }
+    }
+
+    /// Checks if the target is this user, either by key or by username
+    pub fn is_self(&self, target: &User) -> bool {
+        self.key == target.key || self.name.to_lowercase() == target.name.to_lowercase()
+    }
+
+    /// Resolves whether the user is online, which is the case if their last request is no older than the threshold
+    pub fn presence(&self, threshold_nanos: u64, now: u64) -> Presence {
+        Presence {
+            online: now.saturating_sub(self.last_access_stamp) <= threshold_nanos,
+            last_seen_stamp: self.last_access_stamp,
+        }
+    }
+
+    /// Checks if the do not disturb window of the user is currently active
+    pub fn is_do_not_disturb(&self, now: u64) -> bool {
+        let schedule = &self.settings.do_not_disturb;
+        if !schedule.enabled {
+            return false;
+        }
+        match (
+            parse_time_of_day(&schedule.start),
+            parse_time_of_day(&schedule.end),
+            nanos_to_time_of_day(now, &self.timezone),
+        ) {
+            (Some(start), Some(end), Some(time)) => is_within_daily_window(start, end, time),
+            _ => false,
         }
     }
 
@@ -99,6 +306,7 @@ impl User {
         is_friend: bool,
         include_profile: bool,
         timezone: &Tz,
+        online_threshold_nanos: u64,
     ) -> UserPublicInformation {
         let joined_date = if self.settings.show_join_date.is_visible(is_friend) {
             Some(nanos_to_date(self.created_stamp, timezone))
@@ -106,10 +314,14 @@ impl User {
             None
         };
 
-        let last_online_date = if self.settings.show_online_date.is_visible(is_friend) {
-            Some(nanos_to_date(self.last_access_stamp, timezone))
+        let (last_online_date, online) = if self.settings.show_online_date.is_visible(is_friend) {
+            let presence = self.presence(online_threshold_nanos, timestamp_now_nanos());
+            (
+                Some(nanos_to_date(presence.last_seen_stamp, timezone)),
+                Some(presence.online),
+            )
         } else {
-            None
+            (None, None)
         };
 
         let timezone = if self.settings.show_timezone.is_visible(is_friend) {
@@ -129,9 +341,11 @@ impl User {
             display_name: self.display_name.clone(),
             joined_date,
             last_online_date,
+            online,
             permission_level: self.permission_level.clone(),
             profile,
             timezone,
+            mutual_friends_count: None,
         }
     }
 
@@ -155,6 +369,22 @@ impl User {
         Ok(result)
     }
 
+    /// Counts the friends of the user, without friends whose account is pending deletion
+    pub async fn friend_count(
+        &self,
+        user_collection: &Collection<User>,
+        friendship_collection: &Collection<Friendship>,
+    ) -> mongodb::error::Result<u64> {
+        let friend_keys: Vec<String> = self
+            .friends_with_key_and_stamp(friendship_collection)
+            .await?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        let filter = doc! { "key": { "$in": friend_keys }, "delete_after_stamp": null };
+        user_collection.count_documents(filter, None).await
+    }
+
     pub async fn friend_list_with_pagination(
         &self,
         user_collection: &Collection<User>,
@@ -162,6 +392,7 @@ impl User {
         page: u32,
         page_size: u32,
         include_profile: bool,
+        online_threshold_nanos: u64,
     ) -> mongodb::error::Result<FriendList> {
         let friends: Vec<(String, u64)> = self
             .friends_with_key_and_stamp(friendship_collection)
@@ -188,7 +419,12 @@ impl User {
             .zip(friends[start..end].iter().map(|(_, t)| t))
             .filter_map(|(user_option, timestamp)| {
                 user_option.map(|user| FriendInformation {
-                    user: user.public_information(true, include_profile, &self.timezone),
+                    user: user.public_information(
+                        true,
+                        include_profile,
+                        &self.timezone,
+                        online_threshold_nanos,
+                    ),
                     since_date: nanos_to_date(*timestamp, &self.timezone),
                 })
             })
@@ -207,15 +443,143 @@ impl User {
         })
     }
 
+    /// Like `friend_list_with_pagination`, but streams the page of friends straight off the database cursor in no particular order.
+    /// Returns the stream together with the total amount of friends.
+    pub async fn friend_stream_with_pagination(
+        &self,
+        user_collection: &Collection<User>,
+        friendship_collection: &Collection<Friendship>,
+        page: u32,
+        page_size: u32,
+        include_profile: bool,
+        online_threshold_nanos: u64,
+    ) -> mongodb::error::Result<(
+        impl Stream<Item = mongodb::error::Result<FriendInformation>>,
+        u64,
+    )> {
+        let friends: Vec<(String, u64)> = self
+            .friends_with_key_and_stamp(friendship_collection)
+            .await?;
+        let total = friends.len() as u64;
+
+        let since_stamps: HashMap<String, u64> = friends
+            .into_iter()
+            .skip(((page - 1) * page_size) as usize)
+            .take(page_size as usize)
+            .collect();
+        let page_keys: Vec<&String> = since_stamps.keys().collect();
+        let cursor = user_collection
+            .find(
+                doc! { "key": { "$in": page_keys }, "delete_after_stamp": null },
+                None,
+            )
+            .await?;
+
+        let timezone = self.timezone;
+        let friend_information = cursor.map_ok(move |user| {
+            let since_stamp = since_stamps.get(&user.key).copied().unwrap_or_default();
+            FriendInformation {
+                user: user.public_information(
+                    true,
+                    include_profile,
+                    &timezone,
+                    online_threshold_nanos,
+                ),
+                since_date: nanos_to_date(since_stamp, &timezone),
+            }
+        });
+        Ok((friend_information, total))
+    }
+
+    /// Like `friend_list_with_pagination`, but fetches the page of friends in one query and only includes their presence
+    pub async fn friend_presence_list_with_pagination(
+        &self,
+        user_collection: &Collection<User>,
+        friendship_collection: &Collection<Friendship>,
+        page: u32,
+        page_size: u32,
+        favorites_first: bool,
+        online_threshold_nanos: u64,
+    ) -> mongodb::error::Result<FriendPresenceList> {
+        let mut friends: Vec<(String, u64)> = self
+            .friends_with_key_and_stamp(friendship_collection)
+            .await?;
+        if favorites_first {
+            friends.sort_by_key(|(key, _)| !self.favorite_keys.contains(key));
+        }
+
+        let start = ((page - 1) * page_size) as usize;
+        if start >= friends.len() {
+            return Ok(FriendPresenceList {
+                friends: vec![],
+                pagination: Pagination::new(friends.len() as u32, page, page_size, 0),
+            });
+        }
+        let end = std::cmp::min(start + page_size as usize, friends.len());
+
+        let page_keys: Vec<String> = friends[start..end].iter().map(|(k, _)| k.clone()).collect();
+        let users: HashMap<String, User> = find_users_with_keys(user_collection, &page_keys)
+            .await?
+            .into_iter()
+            .map(|user| (user.key.clone(), user))
+            .collect();
+
+        let now = timestamp_now_nanos();
+        let friend_presences = friends[start..end]
+            .iter()
+            .filter_map(|(key, timestamp)| {
+                let user = users.get(key)?;
+                let (online, last_seen_date) = if user.settings.show_online_date.is_visible(true) {
+                    let presence = user.presence(online_threshold_nanos, now);
+                    (
+                        Some(presence.online),
+                        Some(nanos_to_date(presence.last_seen_stamp, &self.timezone)),
+                    )
+                } else {
+                    (None, None)
+                };
+                Some(FriendPresence {
+                    name: user.name.clone(),
+                    display_name: user.display_name.clone(),
+                    online,
+                    last_seen_date,
+                    since_date: nanos_to_date(*timestamp, &self.timezone),
+                    is_favorite: self.favorite_keys.contains(key),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let pagination = Pagination::new(
+            friends.len() as u32,
+            page,
+            page_size,
+            friend_presences.len() as u32,
+        );
+
+        Ok(FriendPresenceList {
+            friends: friend_presences,
+            pagination,
+        })
+    }
+
     pub async fn friend_requests_with_pagination(
         &self,
         collection: &Collection<User>,
         page: u32,
         page_size: u32,
         include_profile: bool,
+        selection: FriendRequestSelection,
+        online_threshold_nanos: u64,
     ) -> mongodb::error::Result<FriendRequests> {
-        let mut requests: Vec<(&String, &u64)> = self.friend_requests.iter().collect();
-        requests.sort_unstable_by(|a, b| b.1.cmp(a.1));
+        let mut requests: Vec<(&String, &u64)> = self
+            .friend_requests
+            .iter()
+            .filter(|(_, &stamp)| selection.since.is_none_or(|since| stamp >= since))
+            .collect();
+        match selection.sort {
+            SortOrder::Newest => requests.sort_unstable_by(|a, b| b.1.cmp(a.1)),
+            SortOrder::Oldest => requests.sort_unstable_by(|a, b| a.1.cmp(b.1)),
+        }
 
         let start = ((page - 1) * page_size) as usize;
         if start >= requests.len() {
@@ -238,7 +602,12 @@ impl User {
             .zip(requests[start..end].iter().map(|(_, &t)| t))
             .filter_map(|(user_option, timestamp)| {
                 user_option.map(|user| FriendRequestInformation {
-                    user: user.public_information(false, include_profile, &self.timezone),
+                    user: user.public_information(
+                        false,
+                        include_profile,
+                        &self.timezone,
+                        online_threshold_nanos,
+                    ),
                     sent_date: nanos_to_date(timestamp, &self.timezone),
                 })
             })
@@ -288,6 +657,7 @@ impl User {
             .filter_map(|(user_option, timestamp)| {
                 user_option.map(|user| BlockListEntry {
                     name: user.name,
+                    display_name: user.display_name,
                     since_date: nanos_to_date(timestamp, &self.timezone),
                 })
             })
@@ -316,6 +686,7 @@ pub async fn find_user_by_key(
     Ok(user)
 }
 
+/// Looks up the users in the order of the given keys, users whose account is pending deletion are left out
 async fn find_users_by_keys(
     collection: &Collection<User>,
     keys: Vec<&str>,
@@ -324,33 +695,465 @@ async fn find_users_by_keys(
         .into_iter()
         .map(|key| find_user_by_key(collection, key))
         .collect::<Vec<_>>();
-    try_join_all(futures).await
+    let users = try_join_all(futures).await?;
+    Ok(users
+        .into_iter()
+        .map(|user| user.filter(|user| user.delete_after_stamp.is_none()))
+        .collect())
 }
 
 pub async fn find_user_by_name(
     collection: &Collection<User>,
     name: &str,
+) -> mongodb::error::Result<Option<User>> {
+    let filter = doc! { "name": name.to_lowercase(), "delete_after_stamp": null };
+    let user = collection.find_one(Some(filter), None).await?;
+    Ok(user)
+}
+
+/// Also finds users whose account is pending deletion, only meant for administrative tools
+pub async fn find_any_user_by_name(
+    collection: &Collection<User>,
+    name: &str,
 ) -> mongodb::error::Result<Option<User>> {
     let filter = doc! { "name": name.to_lowercase() };
     let user = collection.find_one(Some(filter), None).await?;
     Ok(user)
 }
 
+/// Finds the users among the given keys who joined within the given time range, oldest first
+pub async fn find_users_joined_between(
+    collection: &Collection<User>,
+    keys: Vec<String>,
+    from_stamp: u64,
+    to_stamp: u64,
+) -> mongodb::error::Result<Vec<User>> {
+    let filter = doc! {
+        "key": { "$in": keys },
+        "created_stamp": { "$gte": from_stamp as i64, "$lte": to_stamp as i64 },
+        "delete_after_stamp": null,
+    };
+    let find_options = FindOptions::builder()
+        .sort(doc! { "created_stamp": 1 })
+        .build();
+    let cursor = collection.find(filter, find_options).await?;
+    cursor.try_collect().await
+}
+
+/// Finds the users who can be suggested to the viewer as new friends
+pub async fn find_suggestable_users(
+    collection: &Collection<User>,
+    keys: Vec<String>,
+    viewer_key: &str,
+) -> mongodb::error::Result<Vec<User>> {
+    let block_list_key = format!("block_list.{}", viewer_key);
+    let friend_request_key = format!("friend_requests.{}", viewer_key);
+    let filter = doc! {
+        "key": { "$in": keys },
+        "settings.allow_friend_requests": { "$ne": false },
+        "settings.friends_only_discovery": { "$ne": true },
+        "settings.show_in_search": { "$nin": ["Friends", "Private"] },
+        "inactive": { "$ne": true },
+        "delete_after_stamp": null,
+        block_list_key: { "$exists": false },
+        friend_request_key: { "$exists": false },
+    };
+    let cursor = collection.find(filter, None).await?;
+    cursor.try_collect().await
+}
+
+pub async fn find_users_with_keys(
+    collection: &Collection<User>,
+    keys: &[String],
+) -> mongodb::error::Result<Vec<User>> {
+    let filter = doc! { "key": { "$in": keys }, "delete_after_stamp": null };
+    let cursor = collection.find(filter, None).await?;
+    cursor.try_collect().await
+}
+
+pub async fn find_users_by_names(
+    collection: &Collection<User>,
+    names: &[String],
+) -> mongodb::error::Result<Vec<User>> {
+    let filter = doc! { "name": { "$in": names }, "delete_after_stamp": null };
+    let cursor = collection.find(filter, None).await?;
+    cursor.try_collect().await
+}
+
+pub async fn find_user_by_display_name(
+    collection: &Collection<User>,
+    display_name: &str,
+) -> mongodb::error::Result<Option<User>> {
+    let filter = doc! {
+        "name_normalized": normalize_search_term(display_name),
+        "delete_after_stamp": null,
+    };
+    let user = collection.find_one(Some(filter), None).await?;
+    Ok(user)
+}
+
+/// Counts the users who have a pending friend request from the given user
+pub async fn count_sent_friend_requests(
+    collection: &Collection<User>,
+    key: &str,
+) -> mongodb::error::Result<u64> {
+    let friend_request_key = format!("friend_requests.{}", key);
+    let queued_friend_request_key = format!("queued_friend_requests.{}", key);
+    let filter = doc! {
+        "delete_after_stamp": null,
+        "$or": [
+            { friend_request_key: { "$exists": true } },
+            { queued_friend_request_key: { "$exists": true } },
+        ],
+    };
+    collection.count_documents(filter, None).await
+}
+
+pub async fn count_users(collection: &Collection<User>) -> mongodb::error::Result<u64> {
+    collection.count_documents(doc! {}, None).await
+}
+
+/// Counts the users of every permission level, users without a stored level count as regular users
+pub async fn count_users_by_permission_level(
+    collection: &Collection<User>,
+) -> mongodb::error::Result<HashMap<String, u64>> {
+    let pipeline = vec![doc! {
+        "$group": {
+            "_id": { "$ifNull": ["$permission_level", "User"] },
+            "count": { "$sum": 1 },
+        }
+    }];
+
+    let mut cursor = collection.aggregate(pipeline, None).await?;
+    let mut counts = HashMap::new();
+    while let Some(entry) = cursor.try_next().await? {
+        if let (Ok(level), Ok(count)) = (entry.get_str("_id"), entry.get_i32("count")) {
+            counts.insert(level.to_string(), count as u64);
+        }
+    }
+    Ok(counts)
+}
+
+/// The position of a user in join order, the first user to join has rank 1
+pub async fn join_rank(
+    collection: &Collection<User>,
+    created_stamp: u64,
+) -> mongodb::error::Result<u64> {
+    let filter = doc! { "created_stamp": { "$lt": created_stamp as i64 } };
+    Ok(collection.count_documents(filter, None).await? + 1)
+}
+
+pub async fn count_active_users(
+    collection: &Collection<User>,
+    since_stamp: u64,
+) -> mongodb::error::Result<u64> {
+    let filter = doc! { "last_access_stamp": { "$gte": since_stamp as i64 } };
+    collection.count_documents(filter, None).await
+}
+
+pub async fn count_inactive_users(
+    collection: &Collection<User>,
+    cutoff_stamp: u64,
+) -> mongodb::error::Result<u64> {
+    let filter =
+        doc! { "last_access_stamp": { "$lt": cutoff_stamp as i64 }, "inactive": { "$ne": true } };
+    collection.count_documents(filter, None).await
+}
+
+pub async fn flag_inactive_users(
+    collection: &Collection<User>,
+    cutoff_stamp: u64,
+) -> mongodb::error::Result<u64> {
+    let filter =
+        doc! { "last_access_stamp": { "$lt": cutoff_stamp as i64 }, "inactive": { "$ne": true } };
+    let update = doc! { "$set": { "inactive": true } };
+    let result = collection.update_many(filter, update, None).await?;
+    Ok(result.modified_count)
+}
+
+/// Removes the friend requests sent by users who no longer exist, working through the affected users in batches.
+/// Returns the amount of removed requests, running it again after a successful run removes nothing.
+pub async fn remove_orphaned_friend_requests(
+    collection: &Collection<User>,
+    batch_size: usize,
+) -> mongodb::error::Result<u64> {
+    let filter = doc! { "$or": [
+        { "friend_requests": { "$exists": true, "$ne": {} } },
+        { "queued_friend_requests": { "$exists": true, "$ne": {} } },
+    ] };
+    let mut cursor = collection.find(filter, None).await?;
+
+    let mut removed = 0;
+    let mut batch = Vec::with_capacity(batch_size);
+    while let Some(user) = cursor.try_next().await? {
+        batch.push(user);
+        if batch.len() >= batch_size {
+            removed += remove_orphaned_friend_requests_of(collection, &batch).await?;
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        removed += remove_orphaned_friend_requests_of(collection, &batch).await?;
+    }
+    Ok(removed)
+}
+
+async fn remove_orphaned_friend_requests_of(
+    collection: &Collection<User>,
+    users: &[User],
+) -> mongodb::error::Result<u64> {
+    let sender_keys: HashSet<&String> = users
+        .iter()
+        .flat_map(|user| {
+            user.friend_requests
+                .keys()
+                .chain(user.queued_friend_requests.keys())
+        })
+        .collect();
+    let sender_keys: Vec<&String> = sender_keys.into_iter().collect();
+
+    let find_options = FindOptions::builder()
+        .projection(doc! { "_id": 0, "key": 1 })
+        .build();
+    let existing_keys: HashSet<String> = collection
+        .clone_with_type::<Document>()
+        .find(doc! { "key": { "$in": sender_keys } }, find_options)
+        .await?
+        .try_collect::<Vec<Document>>()
+        .await?
+        .iter()
+        .filter_map(|entry| entry.get_str("key").ok().map(str::to_string))
+        .collect();
+
+    let mut removed = 0;
+    for user in users {
+        let mut orphaned = Document::new();
+        for sender_key in user.friend_requests.keys() {
+            if !existing_keys.contains(sender_key) {
+                orphaned.insert(format!("friend_requests.{sender_key}"), "");
+            }
+        }
+        for sender_key in user.queued_friend_requests.keys() {
+            if !existing_keys.contains(sender_key) {
+                orphaned.insert(format!("queued_friend_requests.{sender_key}"), "");
+            }
+        }
+        if orphaned.is_empty() {
+            continue;
+        }
+
+        removed += orphaned.len() as u64;
+        let update = doc! { "$unset": orphaned };
+        collection
+            .update_one(doc! { "key": &user.key }, update, None)
+            .await?;
+    }
+    Ok(removed)
+}
+
+/// Fills in the normalized display name of users which weren't saved since it was introduced, returns how many were updated
+pub async fn backfill_normalized_names(
+    collection: &Collection<User>,
+) -> mongodb::error::Result<u64> {
+    let filter = doc! { "$or": [
+        { "name_normalized": { "$exists": false } },
+        { "name_normalized": "" },
+    ] };
+    let find_options = FindOptions::builder()
+        .projection(doc! { "_id": 0, "key": 1, "display_name": 1 })
+        .build();
+    let mut cursor = collection
+        .clone_with_type::<Document>()
+        .find(filter, find_options)
+        .await?;
+
+    let mut updated = 0;
+    while let Some(entry) = cursor.try_next().await? {
+        let (Ok(key), Ok(display_name)) = (entry.get_str("key"), entry.get_str("display_name"))
+        else {
+            continue;
+        };
+        let update = doc! { "$set": { "name_normalized": normalize_search_term(display_name) } };
+        collection
+            .update_one(doc! { "key": key }, update, None)
+            .await?;
+        updated += 1;
+    }
+    Ok(updated)
+}
+
+/// Finds the users whose deletion grace period ended
+pub async fn find_users_due_for_deletion(
+    collection: &Collection<User>,
+    now: u64,
+) -> mongodb::error::Result<Vec<User>> {
+    let filter = doc! { "delete_after_stamp": { "$ne": null, "$lte": now as i64 } };
+    let cursor = collection.find(filter, None).await?;
+    cursor.try_collect().await
+}
+
+/// Removes the given user from the friend requests, block lists and favorites of all other users
+pub async fn remove_key_references(
+    collection: &Collection<User>,
+    key: &str,
+) -> mongodb::error::Result<u64> {
+    let friend_request_key = format!("friend_requests.{}", key);
+    let queued_friend_request_key = format!("queued_friend_requests.{}", key);
+    let block_list_key = format!("block_list.{}", key);
+    let filter = doc! { "$or": [
+        { &friend_request_key: { "$exists": true } },
+        { &queued_friend_request_key: { "$exists": true } },
+        { &block_list_key: { "$exists": true } },
+        { "favorite_keys": key },
+    ] };
+    let update = doc! {
+        "$unset": { friend_request_key: "", queued_friend_request_key: "", block_list_key: "" },
+        "$pull": { "favorite_keys": key },
+    };
+    let result = collection.update_many(filter, update, None).await?;
+    Ok(result.modified_count)
+}
+
+/// Removes the two users from each other's favorites, used whenever their friendship ends
+pub async fn remove_mutual_favorites(
+    collection: &Collection<User>,
+    first_key: &str,
+    second_key: &str,
+) -> mongodb::error::Result<()> {
+    for (key, favorite_key) in [(first_key, second_key), (second_key, first_key)] {
+        let update = doc! { "$pull": { "favorite_keys": favorite_key } };
+        collection
+            .update_one(doc! { "key": key }, update, None)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Records a settings change if the cooldown since the last one passed, returns false if it didn't.
+/// Checking and recording happen in one update, so concurrent changes can't both pass the check.
+pub async fn claim_settings_change(
+    collection: &Collection<User>,
+    key: &str,
+    cooldown_nanos: u64,
+    now: u64,
+) -> mongodb::error::Result<bool> {
+    let latest_allowed = now.saturating_sub(cooldown_nanos) as i64;
+    let filter = doc! {
+        "key": key,
+        "$or": [
+            { "last_settings_change_stamp": { "$exists": false } },
+            { "last_settings_change_stamp": { "$lte": latest_allowed } },
+        ],
+    };
+    let update = doc! { "$set": { "last_settings_change_stamp": now as i64 } };
+    let result = collection.update_one(filter, update, None).await?;
+    Ok(result.matched_count > 0)
+}
+
+pub async fn delete_user_by_key(
+    collection: &Collection<User>,
+    key: &str,
+) -> mongodb::error::Result<u64> {
+    let result = collection.delete_one(doc! { "key": key }, None).await?;
+    Ok(result.deleted_count)
+}
+
+/// Writes the buffered accesses of a user, only touching the access related fields
+pub async fn flush_access(
+    collection: &Collection<User>,
+    key: &str,
+    access: &PendingAccess,
+) -> mongodb::error::Result<()> {
+    let mut usage_increments = Document::new();
+    for (endpoint, count) in &access.endpoint_usage {
+        // Dots and dollar signs would be interpreted as part of the update path
+        let endpoint = endpoint.replace(['.', '$'], "_");
+        usage_increments.insert(format!("endpoint_usage.{endpoint}"), *count as i64);
+    }
+
+    let filter = doc! { "key": key };
+    let update = doc! {
+        "$max": { "last_access_stamp": access.last_access_stamp as i64 },
+        "$set": { "inactive": false },
+        "$inc": usage_increments,
+    };
+    collection.update_one(filter, update, None).await?;
+    Ok(())
+}
+
+/// Marks a user as online and optionally sets their profile status, only touching these fields
+pub async fn record_presence_ping(
+    collection: &Collection<User>,
+    key: &str,
+    stamp: u64,
+    status: Option<&str>,
+) -> mongodb::error::Result<()> {
+    let mut set = doc! { "inactive": false };
+    if let Some(status) = status {
+        set.insert("profile.status", status);
+    }
+
+    let filter = doc! { "key": key };
+    let update = doc! {
+        "$max": { "last_access_stamp": stamp as i64 },
+        "$set": set,
+    };
+    collection.update_one(filter, update, None).await?;
+    Ok(())
+}
+
+/// Finds the users who have the given user on their block list
+pub async fn find_users_blocking(
+    collection: &Collection<User>,
+    key: &str,
+    page: u32,
+    page_size: u32,
+) -> mongodb::error::Result<(Vec<User>, Pagination)> {
+    let skip = (page - 1) * page_size;
+    let find_options = FindOptions::builder()
+        .skip(skip as u64)
+        .limit(page_size as i64)
+        .sort(doc! { "name": 1 })
+        .build();
+
+    let block_list_key = format!("block_list.{}", key);
+    let filter = doc! { block_list_key: { "$exists": true } };
+    let cursor = collection.find(filter.clone(), find_options).await?;
+    let users: Vec<User> = cursor.try_collect().await?;
+
+    let total: u32 = collection.count_documents(filter, None).await? as u32;
+    let pagination = Pagination::new(total, page, page_size, users.len() as u32);
+
+    Ok((users, pagination))
+}
+
 pub async fn get_public_users(
     collection: &Collection<User>,
     excluded_keys: Vec<String>,
+    friend_keys: Vec<String>,
     viewer_key: &str,
     page: u32,
     page_size: u32,
+    sort: Option<Document>,
 ) -> mongodb::error::Result<(Vec<User>, Pagination)> {
     let skip = (page - 1) * page_size;
     let find_options = FindOptions::builder()
         .skip(skip as u64)
         .limit(page_size as i64)
+        .sort(sort)
         .build();
 
     let block_list_key = format!("block_list.{}", viewer_key);
-    let filter = doc! { "settings.appear_on_public_list": true, "key": {"$nin": excluded_keys}, block_list_key: {"$exists": false}};
+    let filter = doc! {
+        "settings.appear_on_public_list": true,
+        "delete_after_stamp": null,
+        "key": {"$nin": excluded_keys},
+        block_list_key: {"$exists": false},
+        "$or": [
+            {"settings.friends_only_discovery": {"$ne": true}},
+            {"key": {"$in": friend_keys}},
+        ],
+    };
     let mut cursor = collection.find(filter.clone(), find_options).await?;
 
     let mut users = Vec::new();
@@ -363,3 +1166,260 @@ pub async fn get_public_users(
 
     Ok((users, pagination))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::models::user_settings::DoNotDisturbSchedule;
+
+    fn test_user(key: &str, name: &str) -> User {
+        bson::from_document(doc! {
+            "key": key,
+            "name": name,
+            "display_name": name,
+            "created_stamp": 0_i64,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn save_only_sets_access_fields_on_insert() {
+        let update = test_user("key", "name").save_update().unwrap();
+        let set = update.get_document("$set").unwrap();
+        let set_on_insert = update.get_document("$setOnInsert").unwrap();
+
+        for field in ACCESS_FIELDS {
+            assert!(!set.contains_key(field));
+            assert!(set_on_insert.contains_key(field));
+        }
+        assert_eq!(set.get_str("name_normalized").unwrap(), "name");
+    }
+
+    #[test]
+    fn blocking_drops_requests_of_the_user() {
+        let mut user = test_user("key", "name");
+        user.friend_requests.insert("blocked".to_string(), 1);
+        user.queued_friend_requests.insert("blocked".to_string(), 1);
+        user.friend_requests.insert("other".to_string(), 1);
+
+        assert!(user.block_user("blocked").is_ok());
+        assert!(user.block_user("blocked").is_err());
+        assert!(!user.friend_requests.contains_key("blocked"));
+        assert!(!user.queued_friend_requests.contains_key("blocked"));
+        assert!(user.friend_requests.contains_key("other"));
+    }
+
+    #[test]
+    fn releasing_queued_requests_skips_blocked_users() {
+        let mut user = test_user("key", "name");
+        user.queued_friend_requests.insert("blocked".to_string(), 1);
+        user.queued_friend_requests.insert("other".to_string(), 2);
+        user.block_list.insert("blocked".to_string(), 3);
+        user.settings.allow_friend_requests = true;
+
+        user.release_queued_friend_requests();
+        assert!(user.queued_friend_requests.is_empty());
+        assert!(!user.friend_requests.contains_key("blocked"));
+        assert_eq!(user.friend_requests.get("other"), Some(&2));
+    }
+
+    #[test]
+    fn queued_requests_stay_while_requests_are_disallowed() {
+        let mut user = test_user("key", "name");
+        user.queued_friend_requests.insert("other".to_string(), 1);
+        user.settings.allow_friend_requests = false;
+
+        user.release_queued_friend_requests();
+        assert!(user.friend_requests.is_empty());
+        assert_eq!(user.queued_friend_requests.len(), 1);
+    }
+
+    #[test]
+    fn settings_cooldown() {
+        let mut user = test_user("key", "name");
+        assert!(user.can_change_settings(10, 1_000));
+
+        user.last_settings_change_stamp = 100;
+        assert!(!user.can_change_settings(10, 100));
+        assert!(!user.can_change_settings(10, 109));
+        assert!(user.can_change_settings(10, 110));
+        assert!(user.can_change_settings(0, 100));
+    }
+
+    #[test]
+    fn only_friends_can_become_favorites() {
+        let mut user = test_user("key", "name");
+
+        assert_eq!(
+            user.add_favorite("stranger", false),
+            Err("Not friends with the user")
+        );
+        assert!(user.favorite_keys.is_empty());
+
+        assert_eq!(user.add_favorite("friend", true), Ok(()));
+        assert_eq!(
+            user.add_favorite("friend", true),
+            Err("Friend is already a favorite")
+        );
+        assert!(user.favorite_keys.contains("friend"));
+    }
+
+    #[test]
+    fn scheduled_deletion_can_be_restored_within_grace_period() {
+        let mut user = test_user("key", "name");
+        assert_eq!(user.schedule_deletion(100, 50), Ok(150));
+        assert!(user.schedule_deletion(120, 50).is_err());
+
+        assert_eq!(user.restore(149), Ok(()));
+        assert_eq!(user.delete_after_stamp, None);
+        assert!(user.restore(149).is_err());
+    }
+
+    #[test]
+    fn scheduled_deletion_cannot_be_restored_after_grace_period() {
+        let mut user = test_user("key", "name");
+        user.schedule_deletion(100, 50).unwrap();
+
+        assert!(user.restore(150).is_err());
+        assert_eq!(user.delete_after_stamp, Some(150));
+    }
+
+    #[test]
+    fn presence_is_online_within_threshold() {
+        let mut user = test_user("key", "name");
+        user.last_access_stamp = 1_000;
+
+        let presence = user.presence(500, 1_500);
+        assert!(presence.online);
+        assert_eq!(presence.last_seen_stamp, 1_000);
+
+        assert!(!user.presence(500, 1_501).online);
+        assert!(user.presence(500, 900).online);
+    }
+
+    #[test]
+    fn do_not_disturb_follows_schedule() {
+        let noon = 1_713_614_400_000_000_000; // 2024-04-20T12:00:00Z
+        let mut user = test_user("key", "name");
+        user.settings.do_not_disturb = DoNotDisturbSchedule {
+            enabled: true,
+            start: "11:00".to_string(),
+            end: "13:00".to_string(),
+        };
+        assert!(user.is_do_not_disturb(noon));
+
+        user.settings.do_not_disturb.enabled = false;
+        assert!(!user.is_do_not_disturb(noon));
+    }
+
+    #[test]
+    fn do_not_disturb_uses_timezone_of_user() {
+        let noon = 1_713_614_400_000_000_000; // 2024-04-20T12:00:00Z
+        let mut user = test_user("key", "name");
+        user.timezone = Tz::Europe__Berlin;
+        user.settings.do_not_disturb = DoNotDisturbSchedule {
+            enabled: true,
+            start: "11:00".to_string(),
+            end: "13:00".to_string(),
+        };
+        assert!(!user.is_do_not_disturb(noon));
+    }
+
+    #[test]
+    fn reset_settings_records_only_changed_settings() {
+        let mut user = test_user("key", "name");
+        user.settings.appear_on_public_list = true;
+        user.settings.mutual_requests_only = true;
+
+        user.reset_settings(false);
+        assert!(!user.settings.appear_on_public_list);
+        assert!(!user.settings.mutual_requests_only);
+        assert!(user.last_settings_change_stamp > 0);
+
+        let mut changed: Vec<&str> = user
+            .settings_changed_stamps
+            .keys()
+            .map(String::as_str)
+            .collect();
+        changed.sort();
+        assert_eq!(
+            changed,
+            vec!["appear_on_public_list", "mutual_requests_only"]
+        );
+    }
+
+    #[test]
+    fn reset_settings_optionally_resets_display_name() {
+        let mut user = test_user("key", "name");
+        user.display_name = "Display".to_string();
+
+        user.reset_settings(false);
+        assert_eq!(user.display_name, "Display");
+
+        user.reset_settings(true);
+        assert_eq!(user.display_name, "name");
+    }
+
+    #[test]
+    fn reset_settings_releases_queued_requests() {
+        let mut user = test_user("key", "name");
+        user.settings.allow_friend_requests = false;
+        user.queued_friend_requests.insert("sender".to_string(), 1);
+
+        user.reset_settings(false);
+        assert!(user.friend_requests.contains_key("sender"));
+        assert!(user.queued_friend_requests.is_empty());
+    }
+
+    #[test]
+    fn mark_accessed_keeps_latest_stamp() {
+        let mut user = test_user("key", "name");
+        user.inactive = true;
+
+        user.mark_accessed(200);
+        user.mark_accessed(100);
+        assert_eq!(user.last_access_stamp, 200);
+        assert!(!user.inactive);
+    }
+
+    #[test]
+    fn user_information_includes_display_name() {
+        let mut user = test_user("key", "lemon");
+        user.display_name = "Lémon".to_string();
+
+        let public = user.public_information(false, false, &Tz::UTC, 0);
+        assert_eq!(public.name, "lemon");
+        assert_eq!(public.display_name, "Lémon");
+
+        let private = user.private_information();
+        assert_eq!(private.name, "lemon");
+        assert_eq!(private.display_name, "Lémon");
+    }
+
+    #[test]
+    fn is_self_matches_key_or_username() {
+        let user = test_user("key", "lemon");
+
+        assert!(user.is_self(&test_user("key", "other")));
+        assert!(user.is_self(&test_user("other", "LEMON")));
+        assert!(!user.is_self(&test_user("other", "lime")));
+    }
+
+    #[test]
+    fn friendship_window_allows_friendships_below_limit() {
+        let user = test_user("key", "name");
+        assert!(user.can_form_friendship(0, 3));
+        assert!(user.can_form_friendship(2, 3));
+        assert!(!user.can_form_friendship(3, 3));
+    }
+
+    #[test]
+    fn administrators_are_exempt_from_friendship_window() {
+        let mut user = test_user("key", "name");
+        user.permission_level = PermissionLevel::Administrator;
+        assert!(user.can_form_friendship(3, 3));
+
+        user.permission_level = PermissionLevel::Moderator;
+        assert!(!user.can_form_friendship(3, 3));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::api::entities::user::User;
+use crate::api::models::event_models::ServerEvent;
+use crate::api::utils::time_operations::timestamp_now_nanos;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::{broadcast, Notify};
+
+/// The amount of events which are buffered per user before slow receivers start missing events
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Distributes real-time events to all connections of a user, keyed by their API key.
+#[derive(Clone, Default)]
+pub struct EventHub {
+    channels: Arc<Mutex<HashMap<String, broadcast::Sender<ServerEvent>>>>,
+    /// The open connections of every user
+    connections: Arc<Mutex<HashMap<String, Vec<Connection>>>>,
+    next_connection_id: Arc<AtomicU64>,
+}
+
+struct Connection {
+    id: u64,
+    connected_stamp: u64,
+    close: Arc<Notify>,
+}
+
+/// Keeps a connection registered for as long as it is alive, dropping it releases the connection slot.
+pub struct ConnectionGuard {
+    hub: EventHub,
+    key: String,
+    id: u64,
+    close: Arc<Notify>,
+}
+
+impl ConnectionGuard {
+    /// Resolves once the connection was closed using [`EventHub::disconnect`]
+    pub async fn closed(&self) {
+        self.close.notified().await
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut connections = self
+            .hub
+            .connections
+            .lock()
+            .expect("Event hub lock poisoned");
+        if let Some(user_connections) = connections.get_mut(&self.key) {
+            user_connections.retain(|connection| connection.id != self.id);
+            if user_connections.is_empty() {
+                connections.remove(&self.key);
+            }
+        }
+        drop(connections);
+
+        // The receiver of the stream is dropped before its guard, so the channel is unused once it has no receivers left
+        let mut channels = self.hub.channels.lock().expect("Event hub lock poisoned");
+        if channels
+            .get(&self.key)
+            .is_some_and(|sender| sender.receiver_count() == 0)
+        {
+            channels.remove(&self.key);
+        }
+    }
+}
+
+impl EventHub {
+    pub fn subscribe(&self, key: &str) -> broadcast::Receiver<ServerEvent> {
+        let mut channels = self.channels.lock().expect("Event hub lock poisoned");
+        channels
+            .entry(key.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Registers a new connection of the user, returns None if they already hold the maximum amount of connections.
+    pub fn connect(&self, key: &str, max_connections: usize) -> Option<ConnectionGuard> {
+        let mut connections = self.connections.lock().expect("Event hub lock poisoned");
+        let user_connections = connections.entry(key.to_string()).or_default();
+        if user_connections.len() >= max_connections {
+            return None;
+        }
+
+        let id = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
+        let close = Arc::new(Notify::new());
+        user_connections.push(Connection {
+            id,
+            connected_stamp: timestamp_now_nanos(),
+            close: close.clone(),
+        });
+        Some(ConnectionGuard {
+            hub: self.clone(),
+            key: key.to_string(),
+            id,
+            close,
+        })
+    }
+
+    /// Returns the id and connection timestamp of every open connection of the user
+    pub fn connections(&self, key: &str) -> Vec<(u64, u64)> {
+        let connections = self.connections.lock().expect("Event hub lock poisoned");
+        connections
+            .get(key)
+            .map(|user_connections| {
+                user_connections
+                    .iter()
+                    .map(|connection| (connection.id, connection.connected_stamp))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Closes the given connection of the user, returns false if there is no such connection.
+    /// The connection is unregistered once its stream has ended.
+    pub fn disconnect(&self, key: &str, id: u64) -> bool {
+        let connections = self.connections.lock().expect("Event hub lock poisoned");
+        let connection = connections
+            .get(key)
+            .and_then(|user_connections| user_connections.iter().find(|c| c.id == id));
+        match connection {
+            Some(connection) => {
+                connection.close.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Closes every open connection, used on shutdown since the server waits for all streams to end
+    pub fn disconnect_all(&self) {
+        let connections = self.connections.lock().expect("Event hub lock poisoned");
+        for connection in connections.values().flatten() {
+            connection.close.notify_one();
+        }
+    }
+
+    /// Sends the event to every connection of the user, unless they disabled notifications for this kind of event or don't want to be disturbed right now.
+    pub fn notify(&self, receiver: &User, event: ServerEvent) {
+        if receiver.settings.notifications.allows(&event)
+            && !receiver.is_do_not_disturb(timestamp_now_nanos())
+        {
+            self.publish(&receiver.key, event);
+        }
+    }
+
+    /// Sends the event to every connection of the user, does nothing if the user isn't connected.
+    pub fn publish(&self, key: &str, event: ServerEvent) {
+        let mut channels = self.channels.lock().expect("Event hub lock poisoned");
+        if let Some(sender) = channels.get(key) {
+            if sender.send(event).is_err() {
+                channels.remove(key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn disconnect_all_closes_every_connection() {
+        let hub = EventHub::default();
+        let first = hub.connect("first", 5).unwrap();
+        let second = hub.connect("second", 5).unwrap();
+
+        hub.disconnect_all();
+
+        let closed = async {
+            first.closed().await;
+            second.closed().await;
+        };
+        tokio::time::timeout(Duration::from_secs(1), closed)
+            .await
+            .expect("Connections were not closed");
+    }
+
+    #[test]
+    fn connect_enforces_limit_per_user() {
+        let hub = EventHub::default();
+        let first = hub.connect("key", 2).unwrap();
+        let _second = hub.connect("key", 2).unwrap();
+
+        assert!(hub.connect("key", 2).is_none());
+        assert!(hub.connect("other", 2).is_some());
+        assert_eq!(hub.connections("key").len(), 2);
+
+        drop(first);
+        assert_eq!(hub.connections("key").len(), 1);
+        assert!(hub.connect("key", 2).is_some());
+    }
+
+    #[test]
+    fn last_connection_removes_channel() {
+        let hub = EventHub::default();
+        let first_guard = hub.connect("key", 5).unwrap();
+        let first_receiver = hub.subscribe("key");
+        let second_guard = hub.connect("key", 5).unwrap();
+        let second_receiver = hub.subscribe("key");
+
+        drop(first_receiver);
+        drop(first_guard);
+        assert!(hub.channels.lock().unwrap().contains_key("key"));
+
+        drop(second_receiver);
+        drop(second_guard);
+        assert!(hub.channels.lock().unwrap().is_empty());
+        assert!(hub.connections("key").is_empty());
+    }
+}
@@ -1,39 +1,159 @@
-use axum::Router;
-use std::io;
-use utoipa::OpenApi;
+use axum::{http::HeaderValue, middleware, Router};
+use dotenvy::dotenv;
+use std::{io, net::SocketAddr, time::Duration};
 use utoipa_rapidoc::RapiDoc;
 use utoipa_redoc::{Redoc, Servable};
 use utoipa_swagger_ui::SwaggerUi;
 mod api;
+mod config;
 mod docs;
 use crate::api::database::db;
+use crate::api::events::event_hub::EventHub;
+use crate::api::middleware::{compression, ip_rate_limit::IpRateLimiter, security_headers};
+use crate::api::models::response_models::UserList;
 use crate::api::resources;
+use crate::api::utils::access_buffer::AccessBuffer;
+use crate::api::utils::cache::TimedCache;
+use crate::api::utils::limits::MIN_PRESENCE_PING_INTERVAL_SECONDS;
+use crate::api::workers::{
+    access_flusher::AccessFlusher, account_deleter::AccountDeleter,
+    inactive_sweeper::InactiveSweeper, rate_limit_pruner::RateLimitPruner, telemetry::Telemetry,
+    worker::WorkerRegistry,
+};
+use crate::config::Config;
 
 #[derive(Clone)]
 struct AppState {
+    access_buffer: AccessBuffer,
+    config: &'static Config,
     database: db::DB,
+    events: EventHub,
+    ip_rate_limiter: IpRateLimiter,
+    recent_users_cache: TimedCache<UserList>,
+    recent_presence_pings: TimedCache<()>,
+    join_rank_cache: TimedCache<u64>,
 }
 
-#[tokio::main]
-async fn main() -> io::Result<()> {
-    let db = db::setup().await.expect("Failed to set up MongoDB.");
-
-    let app_state = AppState { database: db };
-
-    let app = Router::<AppState>::new()
+fn api_router() -> Router<AppState> {
+    Router::<AppState>::new()
+        .nest("/", resources::admin::router())
+        .nest("/", resources::auth::router())
+        .nest("/", resources::events::router())
         .nest("/", resources::friend::router())
+        .nest("/", resources::limits::router())
         .nest("/", resources::metrics::router())
         .nest("/", resources::notification::router())
         .nest("/", resources::ping::router())
+        .nest("/", resources::presence::router())
+        .nest("/", resources::time::router())
         .nest("/", resources::timezone::router())
         .nest("/", resources::user::router())
         .nest("/", resources::users::router())
-        .merge(SwaggerUi::new("/swagger").url("/api-docs/openapi.json", docs::ApiDoc::openapi()))
-        .merge(Redoc::with_url("/redoc", docs::ApiDoc::openapi()))
+}
+
+#[tokio::main]
+async fn main() -> io::Result<()> {
+    dotenv().expect("Failed to load .env");
+    // Loaded once and shared with every handler through the app state
+    let config: &'static Config = Box::leak(Box::new(
+        Config::from_env().unwrap_or_else(|e| panic!("Invalid configuration: {e}")),
+    ));
+    let db = db::setup(config).await.expect("Failed to set up MongoDB.");
+
+    let app_state = AppState {
+        access_buffer: AccessBuffer::default(),
+        config,
+        database: db,
+        events: EventHub::default(),
+        ip_rate_limiter: IpRateLimiter::new(
+            config.ip_rate_limit_requests,
+            Duration::from_secs(config.ip_rate_limit_window_seconds),
+        ),
+        recent_users_cache: TimedCache::new(Duration::from_secs(30)),
+        recent_presence_pings: TimedCache::new(Duration::from_secs(
+            MIN_PRESENCE_PING_INTERVAL_SECONDS,
+        )),
+        join_rank_cache: TimedCache::new(Duration::from_secs(300)),
+    };
+
+    let workers = WorkerRegistry::default()
+        .register(AccessFlusher)
+        .register(AccountDeleter)
+        .register(InactiveSweeper)
+        .register(RateLimitPruner);
+    let workers = if config.telemetry_enabled {
+        workers.register(Telemetry)
+    } else {
+        workers
+    };
+    let workers = workers.start(app_state.clone());
+    let events = app_state.events.clone();
+
+    // The unprefixed routes stay available for backwards compatibility
+    let app = if config.api_prefix == "/" {
+        api_router()
+    } else {
+        Router::<AppState>::new()
+            .nest(&config.api_prefix, api_router())
+            .merge(api_router())
+    };
+
+    let openapi = docs::ApiDoc::with_servers(&config.api_prefix);
+    let content_security_policy = HeaderValue::from_str(&config.content_security_policy)
+        .expect("Content security policy was validated on startup");
+    let app = app
+        .merge(SwaggerUi::new("/swagger").url("/api-docs/openapi.json", openapi.clone()))
+        .merge(Redoc::with_url("/redoc", openapi))
         .merge(RapiDoc::new("/api-docs/openapi.json").path("/docs"))
+        .layer(middleware::from_fn_with_state(
+            content_security_policy,
+            security_headers::add_security_headers,
+        ))
         .with_state(app_state);
 
+    let app = if config.compression_enabled {
+        app.layer(middleware::from_fn(compression::compress))
+    } else {
+        app
+    };
+
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
     println!("Listening on {}", listener.local_addr()?);
-    axum::serve(listener, app).await
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        // Event streams never end on their own, the server would wait for them forever
+        events.disconnect_all();
+    })
+    .await?;
+
+    workers.shutdown().await;
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
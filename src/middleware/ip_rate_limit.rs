@@ -0,0 +1,188 @@
+use crate::AppState;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::RETRY_AFTER, request::Parts, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Counts the requests of every IP address within fixed time windows
+#[derive(Clone)]
+pub struct IpRateLimiter {
+    max_requests: u32,
+    window: Duration,
+    windows: Arc<Mutex<HashMap<IpAddr, (Instant, u32)>>>,
+}
+
+impl IpRateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        IpRateLimiter {
+            max_requests,
+            window,
+            windows: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Counts a request of the address, returns how long until it can send requests again if it exceeded the limit
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut windows = self.windows.lock().expect("Rate limiter lock poisoned");
+        let (started, count) = windows.entry(ip).or_insert((now, 0));
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+
+        if *count >= self.max_requests {
+            return Err(self.window.saturating_sub(now.duration_since(*started)));
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    /// Forgets the addresses whose window ended, so addresses which stopped sending requests don't pile up
+    pub fn prune(&self) {
+        self.prune_at(Instant::now());
+    }
+
+    fn prune_at(&self, now: Instant) {
+        let mut windows = self.windows.lock().expect("Rate limiter lock poisoned");
+        windows.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+    }
+}
+
+/// Rejects requests with a 429 once their IP address exceeded the limit, meant for endpoints which don't require an API key.
+/// The address is only taken from the trusted proxy header if one is configured, the connecting peer is used otherwise.
+pub struct LimitByIp;
+
+#[async_trait]
+impl FromRequestParts<AppState> for LimitByIp {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(peer) = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let ip = client_ip(&parts.headers, state.config.trusted_proxy_header.as_ref())
+            .unwrap_or(peer.ip());
+
+        if let Err(retry_after) = state.ip_rate_limiter.check(ip) {
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after.as_secs().max(1).to_string())],
+                "Too many requests, try again later",
+            )
+                .into_response());
+        }
+
+        Ok(LimitByIp)
+    }
+}
+
+/// The address the trusted proxy reported, proxies append the address they received the request from as the last entry
+fn client_ip(headers: &HeaderMap, trusted_header: Option<&HeaderName>) -> Option<IpAddr> {
+    headers
+        .get_all(trusted_header?)
+        .iter()
+        .next_back()?
+        .to_str()
+        .ok()?
+        .rsplit(',')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use std::net::Ipv4Addr;
+
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const OTHER_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    #[test]
+    fn limits_requests_within_window() {
+        let limiter = IpRateLimiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter.check_at(IP, now).is_ok());
+        assert!(limiter.check_at(IP, now).is_ok());
+        assert_eq!(
+            limiter.check_at(IP, now + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+        assert!(limiter.check_at(OTHER_IP, now).is_ok());
+    }
+
+    #[test]
+    fn resets_after_window() {
+        let limiter = IpRateLimiter::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter.check_at(IP, now).is_ok());
+        assert!(limiter.check_at(IP, now).is_err());
+        assert!(limiter.check_at(IP, now + Duration::from_secs(60)).is_ok());
+    }
+
+    #[test]
+    fn prune_forgets_ended_windows() {
+        let limiter = IpRateLimiter::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+        limiter.check_at(IP, now).unwrap();
+        limiter
+            .check_at(OTHER_IP, now + Duration::from_secs(30))
+            .unwrap();
+
+        limiter.prune_at(now + Duration::from_secs(60));
+        let windows = limiter.windows.lock().unwrap();
+        assert!(!windows.contains_key(&IP));
+        assert!(windows.contains_key(&OTHER_IP));
+    }
+
+    #[test]
+    fn client_ip_uses_last_entry_of_trusted_header() {
+        let header = HeaderName::from_static("x-forwarded-for");
+        let mut headers = HeaderMap::new();
+        headers.append(&header, HeaderValue::from_static("203.0.113.7"));
+        headers.append(&header, HeaderValue::from_static("198.51.100.1, 192.0.2.1"));
+
+        assert_eq!(client_ip(&headers, Some(&header)), Some(IP));
+    }
+
+    #[test]
+    fn client_ip_ignores_headers_unless_trusted() {
+        let header = HeaderName::from_static("x-forwarded-for");
+        let mut headers = HeaderMap::new();
+        headers.insert(&header, HeaderValue::from_static("192.0.2.1"));
+
+        assert_eq!(client_ip(&headers, None), None);
+        assert_eq!(
+            client_ip(&headers, Some(&HeaderName::from_static("x-real-ip"))),
+            None
+        );
+    }
+
+    #[test]
+    fn client_ip_rejects_invalid_addresses() {
+        let header = HeaderName::from_static("x-forwarded-for");
+        let mut headers = HeaderMap::new();
+        headers.insert(&header, HeaderValue::from_static("not an address"));
+
+        assert_eq!(client_ip(&headers, Some(&header)), None);
+    }
+}
@@ -0,0 +1,71 @@
+use axum::{
+    extract::{Request, State},
+    http::{
+        header::{
+            CONTENT_SECURITY_POLICY, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+        },
+        HeaderMap, HeaderValue,
+    },
+    middleware::Next,
+    response::Response,
+};
+
+/// Adds hardening headers to every response, the CSP is configurable and passed as the middleware state.
+/// Headers already set by a handler are left untouched.
+pub async fn add_security_headers(
+    State(content_security_policy): State<HeaderValue>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    apply_security_headers(response.headers_mut(), content_security_policy);
+    response
+}
+
+fn apply_security_headers(headers: &mut HeaderMap, content_security_policy: HeaderValue) {
+    headers
+        .entry(X_CONTENT_TYPE_OPTIONS)
+        .or_insert(HeaderValue::from_static("nosniff"));
+    headers
+        .entry(REFERRER_POLICY)
+        .or_insert(HeaderValue::from_static("no-referrer"));
+    headers
+        .entry(X_FRAME_OPTIONS)
+        .or_insert(HeaderValue::from_static("DENY"));
+    headers
+        .entry(CONTENT_SECURITY_POLICY)
+        .or_insert(content_security_policy);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSP: HeaderValue = HeaderValue::from_static("default-src 'none'");
+
+    #[test]
+    fn adds_all_security_headers() {
+        let mut headers = HeaderMap::new();
+        apply_security_headers(&mut headers, CSP);
+
+        assert_eq!(headers[X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[REFERRER_POLICY], "no-referrer");
+        assert_eq!(headers[X_FRAME_OPTIONS], "DENY");
+        assert_eq!(headers[CONTENT_SECURITY_POLICY], CSP);
+    }
+
+    #[test]
+    fn keeps_headers_set_by_handlers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("SAMEORIGIN"));
+        headers.insert(
+            CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("default-src 'self'"),
+        );
+        apply_security_headers(&mut headers, CSP);
+
+        assert_eq!(headers[X_FRAME_OPTIONS], "SAMEORIGIN");
+        assert_eq!(headers[CONTENT_SECURITY_POLICY], "default-src 'self'");
+        assert_eq!(headers.get_all(X_FRAME_OPTIONS).iter().count(), 1);
+    }
+}
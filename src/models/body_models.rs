@@ -0,0 +1,44 @@
+use crate::api::utils::limits::{
+    MAX_BLOCK_IMPORT_SIZE, MAX_FRIEND_REQUEST_BATCH_SIZE, MAX_PUBLIC_KEY_LENGTH,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+/// A list of usernames to block at once
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct BlockImport {
+    /// MAX ITEMS: 100 | The usernames to block, do not have to be case-sensitive
+    #[validate(length(min = 1, max = MAX_BLOCK_IMPORT_SIZE))]
+    pub names: Vec<String>,
+}
+
+/// A list of users whose friend requests should be answered at once
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct FriendRequestBatch {
+    /// MAX ITEMS: 50 | The usernames of the senders, do not have to be case-sensitive
+    #[validate(length(min = 1, max = MAX_FRIEND_REQUEST_BATCH_SIZE))]
+    pub names: Vec<String>,
+}
+
+/// A public key to publish for other users, e.g. for end-to-end encryption
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct PublicKeyUpload {
+    /// MAX LENGTH: 8192 | The public key in any text format like PEM or base64, the server doesn't interpret it
+    #[validate(
+        length(min = 1, max = MAX_PUBLIC_KEY_LENGTH),
+        custom(function = "is_printable_ascii")
+    )]
+    pub public_key: String,
+}
+
+fn is_printable_ascii(input: &str) -> Result<(), ValidationError> {
+    if input
+        .chars()
+        .all(|c| c.is_ascii_graphic() || c.is_ascii_whitespace())
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("printable_ascii"))
+    }
+}
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Copy, Clone, ToSchema)]
+#[derive(Serialize, Deserialize, Copy, Clone, ToSchema, PartialEq, Eq)]
 pub enum PrivacyLevel {
     Public,
     Friends,
@@ -9,6 +9,20 @@ pub enum PrivacyLevel {
 }
 
 impl PrivacyLevel {
+    pub const ALL: [PrivacyLevel; 3] = [
+        PrivacyLevel::Public,
+        PrivacyLevel::Friends,
+        PrivacyLevel::Private,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PrivacyLevel::Public => "Public",
+            PrivacyLevel::Friends => "Friends",
+            PrivacyLevel::Private => "Private",
+        }
+    }
+
     pub fn is_visible(&self, is_friend: bool) -> bool {
         match self {
             PrivacyLevel::Public => true,
@@ -18,7 +32,14 @@ impl PrivacyLevel {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, ToSchema, Default)]
+#[derive(Serialize, Deserialize, Copy, Clone, ToSchema, Default)]
+pub enum SortOrder {
+    #[default]
+    Newest,
+    Oldest,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PermissionLevel {
     #[default]
     User,
@@ -26,3 +47,37 @@ pub enum PermissionLevel {
     Administrator,
     Owner,
 }
+
+impl PermissionLevel {
+    pub fn capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = Vec::new();
+        if *self >= PermissionLevel::Moderator {
+            capabilities.push(Capability::ModerateUsers);
+        }
+        if *self >= PermissionLevel::Administrator {
+            capabilities.push(Capability::InspectUsers);
+            capabilities.push(Capability::ManageUsers);
+        }
+        if *self >= PermissionLevel::Owner {
+            capabilities.push(Capability::ManagePermissions);
+        }
+        capabilities
+    }
+
+    pub fn has_capability(&self, capability: Capability) -> bool {
+        self.capabilities().contains(&capability)
+    }
+}
+
+/// Actions a user is allowed to perform based on their permission level
+#[derive(Serialize, Deserialize, Copy, Clone, ToSchema, PartialEq, Eq)]
+pub enum Capability {
+    /// Moderate other users, no endpoint requires it yet, removing friendships falls under `ManageUsers`
+    ModerateUsers,
+    /// View sensitive account details of other users, e.g. who blocked you or an account looked up by its key
+    InspectUsers,
+    /// Use the administrative endpoints, e.g. removing friendships or changing feature flags of other users
+    ManageUsers,
+    /// Change the permission level of other users, no endpoint offers this yet
+    ManagePermissions,
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Real-time events pushed to connected clients
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+#[serde(tag = "type")]
+pub enum ServerEvent {
+    FriendRequestReceived(FriendRequestReceivedEvent),
+    FriendRequestAccepted(FriendRequestAcceptedEvent),
+}
+
+/// Someone sent you a friend request
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+pub struct FriendRequestReceivedEvent {
+    /// The username of the user that sent you the request
+    pub sender_name: String,
+}
+
+/// Someone accepted your friend request
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+pub struct FriendRequestAcceptedEvent {
+    /// The username of the user that accepted your request
+    pub accepter_name: String,
+}
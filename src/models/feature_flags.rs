@@ -0,0 +1,9 @@
+/// Feature flags which can be enabled for individual users to gradually roll out experimental behavior.
+/// Flags which aren't set for a user are treated as disabled.
+///
+/// - `conversations_view`: Opt into the new conversations view while it is being rolled out
+pub const KNOWN_FEATURE_FLAGS: [&str; 1] = ["conversations_view"];
+
+pub fn is_known_feature_flag(name: &str) -> bool {
+    KNOWN_FEATURE_FLAGS.contains(&name)
+}
@@ -7,7 +7,7 @@ use utoipa::ToSchema;
 #[serde(tag = "type")]
 pub enum NotificationResponse {
     TestNotification(TestNotification),
-    FriendRequest(FriendRequestNotification),
+    FriendRequest(Box<FriendRequestNotification>),
 }
 
 /// A list of notifications
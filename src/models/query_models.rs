@@ -1,5 +1,11 @@
-use crate::api::models::enums::PrivacyLevel;
-use crate::api::utils::sanitize;
+use crate::api::models::enums::{PrivacyLevel, SortOrder};
+use crate::api::utils::limits::{
+    MAX_BIO_LENGTH, MAX_INTERESTS, MAX_INTEREST_LENGTH, MAX_MOOD_LENGTH, MAX_PRONOUNS_LENGTH,
+    MAX_STATUS_LENGTH,
+};
+use crate::api::utils::sanitize::{self, InvalidChars};
+use crate::api::utils::time_operations::parse_time_of_day;
+use crate::config::Config;
 use serde::Deserialize;
 use utoipa::IntoParams;
 use validator::Validate;
@@ -17,6 +23,67 @@ impl UserName {
             name: sanitize::alphanumeric(&self.name),
         }
     }
+
+    /// Sanitizes the username, rejecting invalid characters instead of stripping them if strict usernames are enabled
+    pub fn try_sanitize(&self, config: &Config) -> Result<UserName, InvalidChars> {
+        Ok(UserName {
+            name: sanitize_username(&self.name, config)?,
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ApiKeyQuery {
+    /// The API key of the user
+    pub key: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OptionalUserName {
+    /// The username of another user, does not have to be case-sensitive. Yourself if not specified
+    pub name: Option<String>,
+}
+
+impl OptionalUserName {
+    /// Sanitizes the username like `UserName::try_sanitize` if one was given
+    pub fn try_sanitize(&self, config: &Config) -> Result<OptionalUserName, InvalidChars> {
+        Ok(OptionalUserName {
+            name: self
+                .name
+                .as_deref()
+                .map(|name| sanitize_username(name, config))
+                .transpose()?,
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserNamePair {
+    /// The username of the first user, does not have to be case-sensitive
+    pub first_name: String,
+    /// The username of the second user, does not have to be case-sensitive
+    pub second_name: String,
+}
+
+impl UserNamePair {
+    /// Sanitizes both usernames like `UserName::try_sanitize`
+    pub fn try_sanitize(&self, config: &Config) -> Result<UserNamePair, InvalidChars> {
+        Ok(UserNamePair {
+            first_name: sanitize_username(&self.first_name, config)?,
+            second_name: sanitize_username(&self.second_name, config)?,
+        })
+    }
+}
+
+fn sanitize_username(name: &str, config: &Config) -> Result<String, InvalidChars> {
+    if config.strict_usernames {
+        sanitize::try_alphanumeric(name)
+    } else {
+        Ok(sanitize::alphanumeric(name))
+    }
 }
 
 #[derive(Deserialize, IntoParams)]
@@ -27,6 +94,46 @@ pub struct IncludeUserProfile {
     pub include_user_profile: bool,
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FavoritesFirst {
+    /// If your favorite friends should be listed before all other friends
+    #[serde(default = "default_false")]
+    pub favorites_first: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SettingsReset {
+    /// If your display name should be reset to your username as well
+    #[serde(default = "default_false")]
+    pub reset_display_name: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FieldSelection {
+    /// Comma separated names of the user information fields to include (e.g. name,last_online_date), all fields are included if empty
+    pub fields: Option<String>,
+}
+
+impl FieldSelection {
+    pub fn fields(&self) -> Option<Vec<&str>> {
+        let fields: Vec<&str> = self
+            .fields
+            .as_deref()?
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .collect();
+        if fields.is_empty() {
+            None
+        } else {
+            Some(fields)
+        }
+    }
+}
+
 fn default_false() -> bool {
     false
 }
@@ -36,13 +143,25 @@ fn default_false() -> bool {
 pub struct PaginationQuery {
     /// The results page number
     pub page: Option<u32>,
-    /// The maximum amount of results per page, has to be between 1 and 100
+    /// The maximum amount of results per page, has to be between 1 and the max page size (see GET /limits)
     pub page_size: Option<u32>,
 }
 
+impl UserSettingsEdit {
+    /// Checks that the do not disturb times are in the format HH:MM
+    pub fn has_valid_times(&self) -> bool {
+        [&self.dnd_start, &self.dnd_end].iter().all(|time| {
+            time.as_ref()
+                .is_none_or(|time| parse_time_of_day(time).is_some())
+        })
+    }
+}
+
 impl PaginationQuery {
-    pub fn sanitize(&self) -> PaginationQuery {
-        let clamped_page_size = self.page_size.map(|size| size.clamp(1, 100));
+    pub fn sanitize(&self, config: &Config) -> PaginationQuery {
+        let clamped_page_size = self
+            .page_size
+            .map(|size| size.clamp(1, config.max_page_size));
 
         PaginationQuery {
             page: self.page,
@@ -51,6 +170,27 @@ impl PaginationQuery {
     }
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FriendRequestFilter {
+    /// If the newest or oldest requests should be listed first, defaults to newest
+    #[serde(default)]
+    pub sort: SortOrder,
+    /// Only include requests sent at or after this date and time, formatted as RFC 3339 (e.g. 2024-04-20T12:00:00Z)
+    pub since: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FeatureFlagEdit {
+    /// The username of the user whose flag should be changed, does not have to be case-sensitive
+    pub name: String,
+    /// The name of the feature flag, has to be one of the known feature flags
+    pub flag: String,
+    /// If the feature should be enabled for the user
+    pub enabled: bool,
+}
+
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub struct UserSettingsEdit {
@@ -68,51 +208,85 @@ pub struct UserSettingsEdit {
     pub show_timezone: Option<PrivacyLevel>,
     /// If other people should be able to see your profile
     pub show_profile: Option<PrivacyLevel>,
+    /// If other people should be able to see how many friends you have in common with them
+    pub show_mutual_friends: Option<PrivacyLevel>,
+    /// If only your friends should be able to discover you on the public user lists and through display name search
+    pub friends_only_discovery: Option<bool>,
+    /// If friendships should only form once you and the other user both sent each other a request, instead of accepting requests
+    pub mutual_requests_only: Option<bool>,
+    /// If friend requests sent to you should be stored, when disabled they are silently dropped
+    pub reveal_request_received: Option<bool>,
+    /// If you should be notified when someone sends you a friend request
+    pub notify_friend_requests: Option<bool>,
+    /// If you should be notified when someone accepts your friend request
+    pub notify_friend_request_accepted: Option<bool>,
+    /// If no real-time events should be pushed to you during your do not disturb window
+    pub dnd_enabled: Option<bool>,
+    /// When your do not disturb window starts in your timezone (HH:MM)
+    pub dnd_start: Option<String>,
+    /// When your do not disturb window ends in your timezone (HH:MM)
+    pub dnd_end: Option<String>,
 }
 
 #[derive(Deserialize, IntoParams, Validate)]
 #[into_params(parameter_in = Query)]
 pub struct UserProfileEdit {
     /// MAX LENGTH: 64 | Your preferred pronouns
-    #[validate(length(min = 1, max = 64))]
+    #[validate(length(min = 1, max = MAX_PRONOUNS_LENGTH))]
     pub pronouns: Option<String>,
     /// MAX LENGTH: 4096 | Your bio text, it's a custom long text description
-    #[validate(length(min = 1, max = 4096))]
+    #[validate(length(min = 1, max = MAX_BIO_LENGTH))]
     pub bio: Option<String>,
     /// MAX LENGTH: 128 | Your current status, a short text describing your current situation, like being at work or on vacation
-    #[validate(length(min = 1, max = 128))]
+    #[validate(length(min = 1, max = MAX_STATUS_LENGTH))]
     pub status: Option<String>,
     /// MAX LENGTH: 32 | One word describing your current mood
-    #[validate(length(min = 1, max = 32))]
+    #[validate(length(min = 1, max = MAX_MOOD_LENGTH))]
     pub mood: Option<String>,
     /// MAX ITEMS: 20 | List of interests, each a one-word descriptor
-    #[validate(length(min = 0, max = 20))]
+    #[validate(length(min = 0, max = MAX_INTERESTS))]
     pub interests: Option<Vec<String>>,
 }
 
 impl UserProfileEdit {
     pub fn sanitize(&self) -> UserProfileEdit {
         UserProfileEdit {
-            pronouns: self
-                .pronouns
-                .as_ref()
-                .map(|pronouns| sanitize::profanity(&sanitize::limit_string(pronouns, 64))),
-            bio: self
-                .bio
-                .as_ref()
-                .map(|bio| sanitize::profanity(&sanitize::limit_string(bio, 4096))),
-            status: self
-                .status
-                .as_ref()
-                .map(|status| sanitize::profanity(&sanitize::limit_string(status, 128))),
-            mood: self
-                .mood
-                .as_ref()
-                .map(|mood| sanitize::profanity(&sanitize::limit_string(mood, 32))),
-            interests: self
-                .interests
-                .as_ref()
-                .map(|interests| sanitize::limit_strings(interests, 128, true)),
+            pronouns: self.pronouns.as_ref().map(|pronouns| {
+                sanitize::profanity(&sanitize::limit_string(
+                    pronouns,
+                    MAX_PRONOUNS_LENGTH as usize,
+                ))
+            }),
+            bio: self.bio.as_ref().map(|bio| {
+                sanitize::profanity(&sanitize::limit_string(bio, MAX_BIO_LENGTH as usize))
+            }),
+            status: self.status.as_ref().map(|status| {
+                sanitize::profanity(&sanitize::limit_string(status, MAX_STATUS_LENGTH as usize))
+            }),
+            mood: self.mood.as_ref().map(|mood| {
+                sanitize::profanity(&sanitize::limit_string(mood, MAX_MOOD_LENGTH as usize))
+            }),
+            interests: self.interests.as_ref().map(|interests| {
+                sanitize::limit_strings(interests, MAX_INTEREST_LENGTH as usize, true)
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams, Validate)]
+#[into_params(parameter_in = Query)]
+pub struct PresencePingQuery {
+    /// MAX LENGTH: 128 | Sets your profile status if provided
+    #[validate(length(min = 1, max = MAX_STATUS_LENGTH))]
+    pub status: Option<String>,
+}
+
+impl PresencePingQuery {
+    pub fn sanitize(&self) -> PresencePingQuery {
+        PresencePingQuery {
+            status: self.status.as_ref().map(|status| {
+                sanitize::profanity(&sanitize::limit_string(status, MAX_STATUS_LENGTH as usize))
+            }),
         }
     }
 }
@@ -123,3 +297,27 @@ pub struct TimezoneQuery {
     /// The timezone you want to use, not to be case-sensitive. Look up available timezones at GET /timezone.
     pub timezone: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings_edit(value: serde_json::Value) -> UserSettingsEdit {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn do_not_disturb_times_are_optional() {
+        assert!(settings_edit(json!({})).has_valid_times());
+        assert!(
+            settings_edit(json!({ "dnd_start": "22:00", "dnd_end": "07:00" })).has_valid_times()
+        );
+    }
+
+    #[test]
+    fn do_not_disturb_times_must_be_hours_and_minutes() {
+        assert!(!settings_edit(json!({ "dnd_start": "10pm" })).has_valid_times());
+        assert!(!settings_edit(json!({ "dnd_end": "25:00" })).has_valid_times());
+    }
+}
@@ -1,19 +1,144 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use utoipa::ToSchema;
 
-use super::{enums::PermissionLevel, user_profile::UserProfile};
+use super::{
+    enums::{Capability, PermissionLevel},
+    user_profile::UserProfile,
+    user_settings::UserSettings,
+};
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct MessageResponse {
     pub message: String,
 }
 
+/// The account an API key belongs to
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AuthCheck {
+    /// Your username
+    pub name: String,
+    /// Your display name
+    pub display_name: String,
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct CountResponse {
     pub message: String,
     pub count: u64,
 }
 
+/// The amount of users holding each permission level
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PermissionLevelCounts {
+    pub user: u64,
+    pub moderator: u64,
+    pub administrator: u64,
+    pub owner: u64,
+    /// The total amount of users
+    pub total: u64,
+}
+
+/// The amount of your friends and pending friend requests
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendCount {
+    /// The amount of your friends
+    pub friends: u64,
+    /// The amount of friend requests you received and haven't answered yet
+    pub pending_received: u64,
+    /// The amount of friend requests you sent which weren't answered yet
+    pub pending_sent: u64,
+}
+
+/// The amount of your pending friend requests
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendRequestSummary {
+    /// The amount of friend requests you received and haven't answered yet
+    pub incoming: u64,
+    /// The amount of friend requests you sent which weren't answered yet
+    pub outgoing: u64,
+}
+
+/// The outcome of answering a single friend request of a batch
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendRequestBatchOutcome {
+    /// The username as it was given
+    pub name: String,
+    /// The status code the single request endpoint would have responded with
+    pub status: u16,
+    pub message: String,
+}
+
+/// The outcomes of answering multiple friend requests at once
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendRequestBatchResult {
+    pub results: Vec<FriendRequestBatchOutcome>,
+}
+
+/// The optional features this deployment supports
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ServerCapabilities {
+    /// The versioned prefix all endpoints are available under
+    pub api_prefix: String,
+    /// If real-time events can be received via server-sent events on GET /events
+    pub event_stream: bool,
+    /// If real-time events can be received via WebSocket
+    pub websockets: bool,
+    /// If group channels are available
+    pub channels: bool,
+    /// If events can be delivered to webhooks
+    pub webhooks: bool,
+    /// If new accounts can be created through the API
+    pub registration_open: bool,
+    /// If large responses are gzip compressed when requested
+    pub compression: bool,
+    pub limits: ServerLimits,
+}
+
+/// The limits this server enforces on user input
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ServerLimits {
+    /// The amount of results per page if none is specified
+    pub default_page_size: u32,
+    /// The maximum amount of results per page
+    pub max_page_size: u32,
+    /// The maximum length of your pronouns
+    pub max_pronouns_length: u64,
+    /// The maximum length of your bio
+    pub max_bio_length: u64,
+    /// The maximum length of your status
+    pub max_status_length: u64,
+    /// The maximum length of your mood
+    pub max_mood_length: u64,
+    /// The maximum amount of interests on your profile
+    pub max_interests: u64,
+    /// The maximum length of a single interest
+    pub max_interest_length: u64,
+    /// The maximum amount of pending friend requests a user can receive, depending on their permission level
+    pub max_pending_friend_requests: PermissionLevelLimits,
+    /// The maximum amount of simultaneous event stream connections, depending on the permission level
+    pub max_connections: PermissionLevelLimits,
+}
+
+/// Limits depending on the permission level of a user
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+pub struct PermissionLevelLimits {
+    pub user: usize,
+    pub moderator: usize,
+    pub administrator: usize,
+}
+
+impl PermissionLevelLimits {
+    /// Owners share the limit of administrators
+    pub fn for_level(&self, permission_level: &PermissionLevel) -> usize {
+        match permission_level {
+            PermissionLevel::User => self.user,
+            PermissionLevel::Moderator => self.moderator,
+            PermissionLevel::Administrator | PermissionLevel::Owner => self.administrator,
+        }
+    }
+}
+
 /// Private user information only accessible by yourself
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct UserPrivateInformation {
@@ -33,10 +158,167 @@ pub struct UserPrivateInformation {
     pub profile: UserProfile,
     /// Your timezone
     pub timezone: String,
+    /// If your do not disturb window is currently active
+    pub do_not_disturb: bool,
+    /// The date and time your account will be permanently deleted, null if you didn't request its deletion
+    pub deletion_date: Option<String>,
 }
 
-/// Public user information accessible by everyone
+/// Your permission level and the actions it allows you to perform
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserPermissions {
+    /// Your global permission level
+    pub permission_level: PermissionLevel,
+    /// The actions your permission level allows you to perform
+    pub capabilities: Vec<Capability>,
+}
+
+/// Everything a client needs to know about your account on startup
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserAccountSummary {
+    pub user: UserPrivateInformation,
+    pub settings: UserSettingsInformation,
+    pub permissions: UserPermissions,
+    pub friend_count: FriendCount,
+    /// The amount of notifications in your inbox
+    pub notification_count: u64,
+}
+
+/// When an account scheduled for deletion will be permanently deleted
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AccountDeletion {
+    /// The date and time your account will be permanently deleted, until then it can be restored
+    pub deletion_date: String,
+}
+
+/// The automated data lifecycle actions which affect your account
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RetentionStatus {
+    /// If your account is currently flagged as inactive, the flag is removed with your next request to any other endpoint
+    pub inactive: bool,
+    /// The date and time your account will be flagged as inactive if you don't send any further requests, null if inactive accounts aren't flagged on this server
+    pub inactive_date: Option<String>,
+    /// The date and time your account will be permanently deleted, null if you didn't request its deletion
+    pub deletion_date: Option<String>,
+}
+
+/// An endpoint of this API
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+pub struct EndpointInformation {
+    /// The HTTP method, e.g. GET
+    pub method: String,
+    pub path: String,
+}
+
+/// The endpoints you are allowed to use
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AllowedEndpoints {
+    pub endpoints: Vec<EndpointInformation>,
+}
+
+/// The current server time
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ServerTime {
+    /// Nanoseconds since the unix epoch
+    pub timestamp_nanos: u64,
+    /// The time in UTC, formatted as RFC 3339
+    pub rfc3339: String,
+    /// The time in your timezone, formatted like all dates in responses
+    pub date: String,
+    pub supported_formats: Vec<TimeFormat>,
+}
+
+/// A time format used by the API
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TimeFormat {
+    pub name: String,
+    /// Where the format is used
+    pub description: String,
+    /// The current time in this format
+    pub example: String,
+}
+
+/// The public key a user published
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PublicKeyInformation {
+    pub name: String,
+    pub public_key: String,
+}
+
+/// The position of a user in join order
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct JoinRank {
+    pub name: String,
+    /// The user was the n-th member to join, starting at 1
+    pub rank: u64,
+}
+
+/// Whether two users are friends
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendshipExistence {
+    pub first_name: String,
+    pub second_name: String,
+    pub friends: bool,
+}
+
+/// Your user settings and when you last changed them
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserSettingsInformation {
+    #[serde(flatten)]
+    pub settings: UserSettings,
+    /// The date and time each setting was last changed, settings you never changed are not included
+    pub last_changed_dates: HashMap<String, String>,
+}
+
+/// A read-only snapshot of what a user currently sees, used for support
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserSupportSnapshot {
+    pub name: String,
+    pub display_name: String,
+    /// The amount of notifications in the inbox of the user
+    pub notification_count: u64,
+    /// The amount of friends of the user
+    pub friend_count: u64,
+    /// The amount of friend requests the user received and hasn't answered yet
+    pub pending_friend_requests: u64,
+    pub settings: UserSettingsInformation,
+}
+
+/// A single user setting as it is accepted by PATCH /user/settings
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SettingDescriptor {
+    /// The query parameter used to edit this setting
+    pub name: String,
+    /// The current value of this setting
+    pub value: String,
+    /// All values this setting accepts
+    pub options: Vec<String>,
+}
+
+/// Your current user settings including all values each setting accepts
 #[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserSettingsSchema {
+    /// All available settings
+    pub settings: Vec<SettingDescriptor>,
+}
+
+/// Your relationship with another user
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UserRelationship {
+    /// If you are friends with the user
+    pub is_friend: bool,
+    /// If the user sent you a friend request you haven't answered yet
+    pub incoming_request: bool,
+    /// If you sent the user a friend request they haven't answered yet
+    pub outgoing_request: bool,
+    /// If the user is on your block list
+    pub is_blocked: bool,
+    /// If you are on the block list of the user, only visible to administrators
+    pub is_blocked_by: Option<bool>,
+}
+
+/// Public user information accessible by everyone
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct UserPublicInformation {
     /// The name which is used for identification
     pub name: String,
@@ -46,16 +328,20 @@ pub struct UserPublicInformation {
     pub joined_date: Option<String>,
     /// The date and time this account last sent an API request, null if the user set it to private
     pub last_online_date: Option<String>,
+    /// If the user sent an API request within the last few minutes, null if the user set their online date to private
+    pub online: Option<bool>,
     /// The global permission level of the user
     pub permission_level: PermissionLevel,
     /// The profile of the user, not included if not stated in the query
     pub profile: Option<UserProfile>,
     /// The timezone of the user
     pub timezone: Option<String>,
+    /// The amount of friends you have in common with the user, only included when viewing a single user
+    pub mutual_friends_count: Option<u64>,
 }
 
 /// Pagination information for the request results
-#[derive(Serialize, Deserialize, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct Pagination {
     /// The amount of results on the current page
     pub results: u32,
@@ -74,7 +360,7 @@ pub struct Pagination {
 impl Pagination {
     pub fn new(total: u32, page: u32, page_size: u32, results: u32) -> Self {
         let offset = (page - 1) * page_size;
-        let pages_total = (total + page_size - 1) / page_size;
+        let pages_total = total.div_ceil(page_size);
 
         Pagination {
             results,
@@ -88,7 +374,7 @@ impl Pagination {
 }
 
 /// A list of users and their public information
-#[derive(Serialize, Deserialize, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct UserList {
     /// Public user information
     pub users: Vec<UserPublicInformation>,
@@ -129,15 +415,110 @@ pub struct FriendList {
     pub pagination: Pagination,
 }
 
+/// A friend with their current presence
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendPresence {
+    /// The name which is used for identification
+    pub name: String,
+    /// The name which is displayed to others, can be a nickname
+    pub display_name: String,
+    /// If the friend sent an API request within the last few minutes, null if they set their online date to private
+    pub online: Option<bool>,
+    /// The date and time the friend last sent an API request, null if they set their online date to private
+    pub last_seen_date: Option<String>,
+    /// The date you became friends
+    pub since_date: String,
+    /// If you marked the friend as favorite
+    pub is_favorite: bool,
+}
+
+/// The list of your friends with their presence
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendPresenceList {
+    pub friends: Vec<FriendPresence>,
+    pub pagination: Pagination,
+}
+
+/// The result of a presence ping
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PresencePing {
+    /// How many seconds you stay online without sending another request
+    pub online_threshold_seconds: u64,
+}
+
 /// An entry on your block list
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct BlockListEntry {
     /// The username of the blocked user
     pub name: String,
+    /// The display name of the blocked user
+    pub display_name: String,
     /// The date and time you blocked this user
     pub since_date: String,
 }
 
+/// A user who blocked someone
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BlockedByEntry {
+    /// The username of the user who blocked them
+    pub name: String,
+    /// The display name of the user who blocked them
+    pub display_name: String,
+    /// The date and time they were blocked
+    pub since_date: String,
+}
+
+/// The users who blocked a user
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BlockedByList {
+    pub entries: Vec<BlockedByEntry>,
+    pub pagination: Pagination,
+}
+
+/// A friendship between two users
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendGraphEdge {
+    pub source: String,
+    pub target: String,
+}
+
+/// Your friendships and the friendships of your friends as an edge list
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FriendGraph {
+    /// Users who can't be discovered by you are replaced by placeholder names like hidden-1
+    pub edges: Vec<FriendGraphEdge>,
+    /// If not all friendships of your friends could be included
+    pub truncated: bool,
+}
+
+/// An open event stream connection
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SessionInformation {
+    /// The id used to close the connection
+    pub id: u64,
+    /// When the connection was opened
+    pub connected_date: String,
+}
+
+/// Your open event stream connections
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SessionList {
+    pub sessions: Vec<SessionInformation>,
+}
+
+/// The outcome of importing a list of users to block
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BlockImportResult {
+    /// Usernames which were added to your block list
+    pub blocked: Vec<String>,
+    /// Usernames which were not blocked because they are yourself, your friends or already blocked
+    pub skipped: Vec<String>,
+    /// Usernames which don't belong to any user
+    pub unresolved: Vec<String>,
+    /// Usernames which contain invalid characters, as they were sent
+    pub invalid: Vec<String>,
+}
+
 /// Your list of blocked users
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct BlockList {
@@ -145,3 +526,22 @@ pub struct BlockList {
     pub entries: Vec<BlockListEntry>,
     pub pagination: Pagination,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permission_level_limits_for_level() {
+        let limits = PermissionLevelLimits {
+            user: 1,
+            moderator: 2,
+            administrator: 3,
+        };
+
+        assert_eq!(limits.for_level(&PermissionLevel::User), 1);
+        assert_eq!(limits.for_level(&PermissionLevel::Moderator), 2);
+        assert_eq!(limits.for_level(&PermissionLevel::Administrator), 3);
+        assert_eq!(limits.for_level(&PermissionLevel::Owner), 3);
+    }
+}
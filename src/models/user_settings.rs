@@ -1,11 +1,13 @@
 use crate::api::models::enums::PrivacyLevel;
+use crate::api::models::event_models::ServerEvent;
 use crate::api::models::query_models::UserSettingsEdit;
+use crate::api::models::response_models::SettingDescriptor;
 use axum::extract::Query;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 /// User configuration
-#[derive(Serialize, Deserialize, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct UserSettings {
     /// If people can see you on the public user list
     #[serde(default = "default_false")]
@@ -28,6 +30,74 @@ pub struct UserSettings {
     /// If people can see your profile
     #[serde(default = "default_public")]
     pub show_profile: PrivacyLevel,
+    /// If people can see how many friends you have in common with them
+    #[serde(default = "default_public")]
+    pub show_mutual_friends: PrivacyLevel,
+    /// If only your friends can discover you on the public user list and through display name search
+    #[serde(default = "default_false")]
+    pub friends_only_discovery: bool,
+    /// If friendships should only form once you and the other user both sent each other a request, instead of accepting requests
+    #[serde(default = "default_false")]
+    pub mutual_requests_only: bool,
+    /// If friend requests sent to you are stored at all, when disabled they are silently dropped
+    #[serde(default = "default_true")]
+    pub reveal_request_received: bool,
+    /// Which real-time events are pushed to you
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+    /// A daily time window in your timezone during which no real-time events are pushed to you
+    #[serde(default)]
+    pub do_not_disturb: DoNotDisturbSchedule,
+}
+
+/// A daily window in which real-time events are held back, events are still stored and can be retrieved later
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+pub struct DoNotDisturbSchedule {
+    /// If the schedule is active
+    pub enabled: bool,
+    /// When the window starts (HH:MM)
+    pub start: String,
+    /// When the window ends (HH:MM), if it is before the start the window spans midnight
+    pub end: String,
+}
+
+impl Default for DoNotDisturbSchedule {
+    fn default() -> Self {
+        DoNotDisturbSchedule {
+            enabled: false,
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+        }
+    }
+}
+
+/// Controls which events are pushed to your open connections
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+pub struct NotificationSettings {
+    /// If you are notified when someone sends you a friend request
+    #[serde(default = "default_true")]
+    pub friend_requests: bool,
+    /// If you are notified when someone accepts your friend request
+    #[serde(default = "default_true")]
+    pub friend_request_accepted: bool,
+}
+
+impl NotificationSettings {
+    pub fn allows(&self, event: &ServerEvent) -> bool {
+        match event {
+            ServerEvent::FriendRequestReceived(_) => self.friend_requests,
+            ServerEvent::FriendRequestAccepted(_) => self.friend_request_accepted,
+        }
+    }
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            friend_requests: true,
+            friend_request_accepted: true,
+        }
+    }
 }
 
 fn default_private() -> PrivacyLevel {
@@ -46,32 +116,176 @@ fn default_false() -> bool {
     false
 }
 
-impl UserSettings {
-    pub fn update(&mut self, data: Query<UserSettingsEdit>) {
-        if let Some(new_value) = &data.appear_on_public_list {
-            self.appear_on_public_list = *new_value;
-        }
-        if let Some(new_value) = &data.show_join_date {
-            self.show_join_date = *new_value;
-        }
-        if let Some(new_value) = &data.show_online {
-            self.show_online_date = *new_value;
-        }
-        if let Some(new_value) = &data.show_in_search {
-            self.show_in_search = *new_value;
-        }
-        if let Some(new_value) = &data.allow_friend_requests {
-            self.allow_friend_requests = *new_value;
-        }
-        if let Some(new_value) = &data.show_timezone {
-            self.show_timezone = *new_value;
-        }
-        if let Some(new_value) = &data.show_profile {
-            self.show_profile = *new_value;
+fn update_setting<T: PartialEq>(
+    setting: &mut T,
+    new_value: Option<T>,
+    name: &'static str,
+    changed: &mut Vec<&'static str>,
+) {
+    if let Some(new_value) = new_value {
+        if *setting != new_value {
+            *setting = new_value;
+            changed.push(name);
         }
     }
 }
 
+fn bool_setting(name: &str, value: bool) -> SettingDescriptor {
+    SettingDescriptor {
+        name: name.to_string(),
+        value: value.to_string(),
+        options: vec!["true".to_string(), "false".to_string()],
+    }
+}
+
+fn time_setting(name: &str, value: &str) -> SettingDescriptor {
+    SettingDescriptor {
+        name: name.to_string(),
+        value: value.to_string(),
+        options: vec!["HH:MM".to_string()],
+    }
+}
+
+fn privacy_setting(name: &str, value: PrivacyLevel) -> SettingDescriptor {
+    SettingDescriptor {
+        name: name.to_string(),
+        value: value.as_str().to_string(),
+        options: PrivacyLevel::ALL
+            .iter()
+            .map(|level| level.as_str().to_string())
+            .collect(),
+    }
+}
+
+impl UserSettings {
+    /// Describes every setting using the parameter names of [`UserSettingsEdit`]
+    pub fn schema(&self) -> Vec<SettingDescriptor> {
+        vec![
+            bool_setting("appear_on_public_list", self.appear_on_public_list),
+            privacy_setting("show_join_date", self.show_join_date),
+            privacy_setting("show_online", self.show_online_date),
+            privacy_setting("show_in_search", self.show_in_search),
+            bool_setting("allow_friend_requests", self.allow_friend_requests),
+            privacy_setting("show_timezone", self.show_timezone),
+            privacy_setting("show_profile", self.show_profile),
+            privacy_setting("show_mutual_friends", self.show_mutual_friends),
+            bool_setting("friends_only_discovery", self.friends_only_discovery),
+            bool_setting("mutual_requests_only", self.mutual_requests_only),
+            bool_setting("reveal_request_received", self.reveal_request_received),
+            bool_setting("notify_friend_requests", self.notifications.friend_requests),
+            bool_setting(
+                "notify_friend_request_accepted",
+                self.notifications.friend_request_accepted,
+            ),
+            bool_setting("dnd_enabled", self.do_not_disturb.enabled),
+            time_setting("dnd_start", &self.do_not_disturb.start),
+            time_setting("dnd_end", &self.do_not_disturb.end),
+        ]
+    }
+
+    /// Applies all given values and returns the names of the settings which actually changed
+    pub fn update(&mut self, data: Query<UserSettingsEdit>) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        update_setting(
+            &mut self.appear_on_public_list,
+            data.appear_on_public_list,
+            "appear_on_public_list",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.show_join_date,
+            data.show_join_date,
+            "show_join_date",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.show_online_date,
+            data.show_online,
+            "show_online",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.show_in_search,
+            data.show_in_search,
+            "show_in_search",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.allow_friend_requests,
+            data.allow_friend_requests,
+            "allow_friend_requests",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.show_timezone,
+            data.show_timezone,
+            "show_timezone",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.show_profile,
+            data.show_profile,
+            "show_profile",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.show_mutual_friends,
+            data.show_mutual_friends,
+            "show_mutual_friends",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.friends_only_discovery,
+            data.friends_only_discovery,
+            "friends_only_discovery",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.mutual_requests_only,
+            data.mutual_requests_only,
+            "mutual_requests_only",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.reveal_request_received,
+            data.reveal_request_received,
+            "reveal_request_received",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.notifications.friend_requests,
+            data.notify_friend_requests,
+            "notify_friend_requests",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.notifications.friend_request_accepted,
+            data.notify_friend_request_accepted,
+            "notify_friend_request_accepted",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.do_not_disturb.enabled,
+            data.dnd_enabled,
+            "dnd_enabled",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.do_not_disturb.start,
+            data.dnd_start.clone(),
+            "dnd_start",
+            &mut changed,
+        );
+        update_setting(
+            &mut self.do_not_disturb.end,
+            data.dnd_end.clone(),
+            "dnd_end",
+            &mut changed,
+        );
+        changed
+    }
+}
+
 impl Default for UserSettings {
     fn default() -> Self {
         UserSettings {
@@ -82,6 +296,42 @@ impl Default for UserSettings {
             allow_friend_requests: true,
             show_timezone: PrivacyLevel::Private,
             show_profile: PrivacyLevel::Public,
+            show_mutual_friends: PrivacyLevel::Public,
+            friends_only_discovery: false,
+            mutual_requests_only: false,
+            reveal_request_received: true,
+            notifications: NotificationSettings::default(),
+            do_not_disturb: DoNotDisturbSchedule::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{self, doc};
+    use serde_json::json;
+
+    fn edit(value: serde_json::Value) -> Query<UserSettingsEdit> {
+        Query(serde_json::from_value(value).unwrap())
+    }
+
+    #[test]
+    fn mutual_requests_only_defaults_to_off() {
+        let settings: UserSettings = bson::from_document(doc! {}).unwrap();
+        assert!(!settings.mutual_requests_only);
+        assert!(!UserSettings::default().mutual_requests_only);
+    }
+
+    #[test]
+    fn update_reports_mutual_requests_only_change() {
+        let mut settings = UserSettings::default();
+
+        let changed = settings.update(edit(json!({ "mutual_requests_only": true })));
+        assert!(settings.mutual_requests_only);
+        assert_eq!(changed, vec!["mutual_requests_only"]);
+
+        let changed = settings.update(edit(json!({ "mutual_requests_only": true })));
+        assert!(changed.is_empty());
+    }
+}
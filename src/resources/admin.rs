@@ -0,0 +1,595 @@
+use crate::api::entities::audit_log::{AuditAction, AuditLogEntry};
+use crate::api::entities::friendship::{
+    are_friends, find_friendship_by_keys, remove_friendship_by_id,
+};
+use crate::api::entities::notification::count_notifications_by_receiver_key;
+use crate::api::entities::user::{
+    count_inactive_users, count_users_by_permission_level, find_any_user_by_name, find_user_by_key,
+    find_users_blocking, remove_mutual_favorites, remove_orphaned_friend_requests,
+};
+use crate::api::models::feature_flags::is_known_feature_flag;
+use crate::api::models::query_models::{
+    ApiKeyQuery, FeatureFlagEdit, PaginationQuery, UserName, UserNamePair,
+};
+use crate::api::models::response_models::{
+    BlockedByEntry, BlockedByList, CountResponse, FriendshipExistence, PermissionLevelCounts,
+    UserSupportSnapshot,
+};
+use crate::api::security::authentication::RequireAdmin;
+use crate::api::utils::limits::ORPHAN_CLEANUP_BATCH_SIZE;
+use crate::api::utils::time_operations::nanos_to_date;
+use crate::api::workers::inactive_sweeper::inactive_cutoff_stamp;
+use crate::{unpack_result, unpack_result_bad_request, unpack_result_option, AppState};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, patch, post};
+use axum::{Json, Router};
+
+/// Force-remove a friendship.
+// region: delete_admin_friend
+/// This endpoint allows administrators to remove the friendship between two users without their consent.
+#[utoipa::path(
+    delete,
+    path = "/admin/friend",
+    params(UserNamePair),
+    responses(
+        (status = 200, description = "Friendship successfully removed"),
+        (status = 400, description = "Username contains invalid characters"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "User not found or users are not friends"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn delete_admin_friend(
+    RequireAdmin(user): RequireAdmin,
+    State(state): State<AppState>,
+    query: Query<UserNamePair>,
+) -> Response {
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
+
+    let first = unpack_result_option!(
+        find_any_user_by_name(&state.database.user_collection, &query.first_name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occured while fetching user"
+    );
+
+    let second = unpack_result_option!(
+        find_any_user_by_name(&state.database.user_collection, &query.second_name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occured while fetching user"
+    );
+
+    let friendship = unpack_result_option!(
+        find_friendship_by_keys(
+            &state.database.friendship_collection,
+            vec![first.key.clone(), second.key.clone()],
+        )
+        .await,
+        StatusCode::NOT_FOUND,
+        "Users are not friends",
+        "An error occured while fetching friendship"
+    );
+
+    // Recorded first, so the removal never happens without an audit log entry
+    unpack_result!(
+        AuditLogEntry::record(
+            &state.database.audit_log_collection,
+            AuditAction::FriendshipRemoved,
+            &user.key,
+            vec![first.key.clone(), second.key.clone()],
+        )
+        .await,
+        "An error occured while writing the audit log"
+    );
+
+    unpack_result!(
+        remove_friendship_by_id(
+            &state.database.friendship_collection,
+            &friendship.id.unwrap(),
+        )
+        .await,
+        "An error occured while removing friendship"
+    );
+
+    unpack_result!(
+        remove_mutual_favorites(&state.database.user_collection, &first.key, &second.key).await,
+        "An error occured while removing favorites"
+    );
+
+    (StatusCode::OK, "Friendship successfully removed").into_response()
+}
+// endregion: delete_admin_friend
+
+/// Check if two users are friends.
+// region: get_admin_friend_exists
+/// This endpoint allows administrators to verify whether two users are friends, without changing anything.
+#[utoipa::path(
+    get,
+    path = "/admin/friend/exists",
+    params(UserNamePair),
+    responses(
+        (status = 200, description = "Whether the users are friends", body = FriendshipExistence),
+        (status = 400, description = "Username contains invalid characters"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn get_admin_friend_exists(
+    RequireAdmin(user): RequireAdmin,
+    State(state): State<AppState>,
+    query: Query<UserNamePair>,
+) -> Response {
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
+
+    let first = unpack_result_option!(
+        find_any_user_by_name(&state.database.user_collection, &query.first_name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occured while fetching user"
+    );
+
+    let second = unpack_result_option!(
+        find_any_user_by_name(&state.database.user_collection, &query.second_name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occured while fetching user"
+    );
+
+    let friends = unpack_result!(
+        are_friends(
+            &state.database.friendship_collection,
+            vec![first.key.clone(), second.key.clone()],
+        )
+        .await,
+        "An error occured while fetching friendship"
+    );
+
+    unpack_result!(
+        AuditLogEntry::record(
+            &state.database.audit_log_collection,
+            AuditAction::FriendshipLookedUp,
+            &user.key,
+            vec![first.key, second.key],
+        )
+        .await,
+        "An error occured while writing the audit log"
+    );
+
+    Json(FriendshipExistence {
+        first_name: first.name,
+        second_name: second.name,
+        friends,
+    })
+    .into_response()
+}
+// endregion: get_admin_friend_exists
+
+/// Look up a user by API key.
+// region: get_admin_user_by_key
+/// This endpoint allows administrators to find the account an API key belongs to.
+/// The key itself is not part of the response.
+#[utoipa::path(
+    get,
+    path = "/admin/user/by-key",
+    params(ApiKeyQuery),
+    responses(
+        (status = 200, description = "The account the key belongs to", body = UserPrivateInformation),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn get_admin_user_by_key(
+    RequireAdmin(user): RequireAdmin,
+    State(state): State<AppState>,
+    query: Query<ApiKeyQuery>,
+) -> Response {
+    let target = unpack_result_option!(
+        find_user_by_key(&state.database.user_collection, &query.key).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occured while fetching user"
+    );
+
+    unpack_result!(
+        AuditLogEntry::record(
+            &state.database.audit_log_collection,
+            AuditAction::UserLookedUpByKey,
+            &user.key,
+            vec![target.key.clone()],
+        )
+        .await,
+        "An error occured while writing the audit log"
+    );
+
+    Json(target.private_information()).into_response()
+}
+// endregion: get_admin_user_by_key
+
+/// View a user like they see themselves.
+// region: get_admin_impersonate
+/// This endpoint allows administrators to see a read-only snapshot of what a user currently sees, e.g. to diagnose support requests.
+/// Every access is recorded in the audit log.
+#[utoipa::path(
+    get,
+    path = "/admin/impersonate",
+    params(UserName),
+    responses(
+        (status = 200, description = "A snapshot of the user's view", body = UserSupportSnapshot),
+        (status = 400, description = "Username contains invalid characters"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn get_admin_impersonate(
+    RequireAdmin(user): RequireAdmin,
+    State(state): State<AppState>,
+    query: Query<UserName>,
+) -> Response {
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
+
+    let target = unpack_result_option!(
+        find_any_user_by_name(&state.database.user_collection, &query.name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occured while fetching user"
+    );
+
+    unpack_result!(
+        AuditLogEntry::record(
+            &state.database.audit_log_collection,
+            AuditAction::UserImpersonated,
+            &user.key,
+            vec![target.key.clone()],
+        )
+        .await,
+        "An error occured while writing the audit log"
+    );
+
+    let notification_count = unpack_result!(
+        count_notifications_by_receiver_key(&state.database.notification_collection, &target.key)
+            .await,
+        "An error occured while counting notifications"
+    );
+
+    let friend_count = unpack_result!(
+        target
+            .friend_count(
+                &state.database.user_collection,
+                &state.database.friendship_collection
+            )
+            .await,
+        "An error occured while counting friends"
+    );
+
+    Json(UserSupportSnapshot {
+        name: target.name.clone(),
+        display_name: target.display_name.clone(),
+        notification_count,
+        friend_count,
+        pending_friend_requests: target.friend_requests.len() as u64,
+        settings: target.settings_information(),
+    })
+    .into_response()
+}
+// endregion: get_admin_impersonate
+
+/// List who blocked a user.
+// region: get_admin_user_blocked_by
+/// This endpoint allows administrators to see which users have blocked a user, e.g. when investigating harassment.
+/// Every access is recorded in the audit log.
+#[utoipa::path(
+    get,
+    path = "/admin/user/blocked-by",
+    params(UserName, PaginationQuery),
+    responses(
+        (status = 200, description = "The users who blocked the user", body = BlockedByList),
+        (status = 400, description = "Username contains invalid characters"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn get_admin_user_blocked_by(
+    RequireAdmin(user): RequireAdmin,
+    State(state): State<AppState>,
+    query: Query<UserName>,
+    pagination: Query<PaginationQuery>,
+) -> Response {
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
+    let pagination = pagination.sanitize(state.config);
+    let page = pagination.page.unwrap_or(1);
+    let page_size = pagination
+        .page_size
+        .unwrap_or(state.config.default_page_size);
+
+    let target = unpack_result_option!(
+        find_any_user_by_name(&state.database.user_collection, &query.name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occured while fetching user"
+    );
+
+    unpack_result!(
+        AuditLogEntry::record(
+            &state.database.audit_log_collection,
+            AuditAction::BlockedByLookedUp,
+            &user.key,
+            vec![target.key.clone()],
+        )
+        .await,
+        "An error occured while writing the audit log"
+    );
+
+    let (blockers, pagination) = unpack_result!(
+        find_users_blocking(
+            &state.database.user_collection,
+            &target.key,
+            page,
+            page_size
+        )
+        .await,
+        "An error occured while fetching users"
+    );
+
+    let entries = blockers
+        .iter()
+        .map(|blocker| BlockedByEntry {
+            name: blocker.name.clone(),
+            display_name: blocker.display_name.clone(),
+            since_date: blocker
+                .block_list
+                .get(&target.key)
+                .map(|stamp| nanos_to_date(*stamp, &user.timezone))
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    Json(BlockedByList {
+        entries,
+        pagination,
+    })
+    .into_response()
+}
+// endregion: get_admin_user_blocked_by
+
+/// Set a feature flag of a user.
+// region: patch_admin_user_flags
+/// This endpoint allows administrators to enable or disable experimental features for a user.
+#[utoipa::path(
+    patch,
+    path = "/admin/user/flags",
+    params(FeatureFlagEdit),
+    responses(
+        (status = 200, description = "The updated feature flags of the user", body = HashMap<String, bool>),
+        (status = 400, description = "Unknown feature flag or username contains invalid characters"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn patch_admin_user_flags(
+    RequireAdmin(user): RequireAdmin,
+    State(state): State<AppState>,
+    query: Query<FeatureFlagEdit>,
+) -> Response {
+    if !is_known_feature_flag(&query.flag) {
+        return (StatusCode::BAD_REQUEST, "Unknown feature flag").into_response();
+    }
+
+    let target_name = UserName {
+        name: query.name.clone(),
+    };
+    let target_name = unpack_result_bad_request!(target_name.try_sanitize(state.config));
+
+    let mut target = unpack_result_option!(
+        find_any_user_by_name(&state.database.user_collection, &target_name.name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occured while fetching user"
+    );
+
+    // Recorded first, so the change never happens without an audit log entry
+    unpack_result!(
+        AuditLogEntry::record(
+            &state.database.audit_log_collection,
+            AuditAction::FeatureFlagChanged,
+            &user.key,
+            vec![target.key.clone()],
+        )
+        .await,
+        "An error occured while writing the audit log"
+    );
+
+    target
+        .feature_flags
+        .insert(query.flag.clone(), query.enabled);
+    unpack_result!(
+        target.save(&state.database.user_collection).await,
+        "An error occured while saving user"
+    );
+
+    Json(target.resolved_feature_flags()).into_response()
+}
+// endregion: patch_admin_user_flags
+
+/// Count inactive accounts.
+// region: get_admin_stats_inactive
+/// This endpoint returns how many accounts the inactive account sweeper would currently flag.
+#[utoipa::path(
+    get,
+    path = "/admin/stats/inactive",
+    responses(
+        (status = 200, description = "Amount of inactive accounts which aren't flagged yet", body = CountResponse),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn get_admin_stats_inactive(
+    RequireAdmin(_): RequireAdmin,
+    State(state): State<AppState>,
+) -> Response {
+    let count = unpack_result!(
+        count_inactive_users(
+            &state.database.user_collection,
+            inactive_cutoff_stamp(state.config.inactive_account_days)
+        )
+        .await,
+        "An error occured while counting inactive users"
+    );
+
+    Json(CountResponse {
+        message: format!("Found {} inactive account(s)", count),
+        count,
+    })
+    .into_response()
+}
+// endregion: get_admin_stats_inactive
+
+/// Count users by permission level.
+// region: get_admin_stats_permissions
+/// This endpoint returns how many users hold each permission level, along with the total amount of users.
+#[utoipa::path(
+    get,
+    path = "/admin/stats/permissions",
+    responses(
+        (status = 200, description = "Amount of users per permission level", body = PermissionLevelCounts),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn get_admin_stats_permissions(
+    RequireAdmin(_): RequireAdmin,
+    State(state): State<AppState>,
+) -> Response {
+    let counts = unpack_result!(
+        count_users_by_permission_level(&state.database.user_collection).await,
+        "An error occured while counting users"
+    );
+
+    let count_of = |level: &str| counts.get(level).copied().unwrap_or(0);
+    Json(PermissionLevelCounts {
+        user: count_of("User"),
+        moderator: count_of("Moderator"),
+        administrator: count_of("Administrator"),
+        owner: count_of("Owner"),
+        total: counts.values().sum(),
+    })
+    .into_response()
+}
+// endregion: get_admin_stats_permissions
+
+/// Remove orphaned friend requests.
+// region: post_admin_maintenance_cleanup_requests
+/// This endpoint removes pending and queued friend requests which were sent by accounts that no longer exist.
+/// The users are processed in batches and the cleanup can safely be run again, it will only remove what is left over.
+#[utoipa::path(
+    post,
+    path = "/admin/maintenance/cleanup-requests",
+    responses(
+        (status = 200, description = "Amount of removed friend requests", body = CountResponse),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Admin"
+)]
+async fn post_admin_maintenance_cleanup_requests(
+    RequireAdmin(user): RequireAdmin,
+    State(state): State<AppState>,
+) -> Response {
+    // Recorded first, so the cleanup never happens without an audit log entry
+    let entry_id = unpack_result!(
+        AuditLogEntry::record(
+            &state.database.audit_log_collection,
+            AuditAction::OrphanedFriendRequestsRemoved,
+            &user.key,
+            vec![],
+        )
+        .await,
+        "An error occured while writing the audit log"
+    );
+
+    let count = unpack_result!(
+        remove_orphaned_friend_requests(&state.database.user_collection, ORPHAN_CLEANUP_BATCH_SIZE)
+            .await,
+        "An error occured while removing orphaned friend requests"
+    );
+
+    unpack_result!(
+        AuditLogEntry::set_affected_count(&state.database.audit_log_collection, &entry_id, count)
+            .await,
+        "An error occured while writing the audit log"
+    );
+
+    Json(CountResponse {
+        message: format!("Removed {} orphaned friend request(s)", count),
+        count,
+    })
+    .into_response()
+}
+// endregion: post_admin_maintenance_cleanup_requests
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new()
+        .route("/admin/friend", delete(delete_admin_friend))
+        .route("/admin/friend/exists", get(get_admin_friend_exists))
+        .route("/admin/impersonate", get(get_admin_impersonate))
+        .route(
+            "/admin/maintenance/cleanup-requests",
+            post(post_admin_maintenance_cleanup_requests),
+        )
+        .route("/admin/stats/inactive", get(get_admin_stats_inactive))
+        .route("/admin/stats/permissions", get(get_admin_stats_permissions))
+        .route("/admin/user/blocked-by", get(get_admin_user_blocked_by))
+        .route("/admin/user/by-key", get(get_admin_user_by_key))
+        .route("/admin/user/flags", patch(patch_admin_user_flags))
+}
@@ -0,0 +1,60 @@
+use crate::api::models::response_models::{AllowedEndpoints, AuthCheck};
+use crate::api::security::access::allowed_endpoints;
+use crate::api::security::authentication::{ExtractUser, ExtractUserReadOnly};
+use crate::AppState;
+use axum::{routing::get, Json, Router};
+
+/// Check your API key.
+// region: get_auth_check
+/// This endpoint returns the username your API key belongs to.
+/// Unlike all other endpoints it doesn't count as activity, your last online date and endpoint usage stay untouched.
+#[utoipa::path(
+    get,
+    path = "/auth/check",
+    responses(
+        (status = 200, description = "The API key is valid", body = AuthCheck),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Misc"
+)]
+async fn get_auth_check(ExtractUserReadOnly(user): ExtractUserReadOnly) -> Json<AuthCheck> {
+    Json(AuthCheck {
+        name: user.name,
+        display_name: user.display_name,
+    })
+}
+// endregion: get_auth_check
+
+/// List the endpoints you can use.
+// region: get_auth_capabilities
+/// This endpoint returns every endpoint your permission level allows you to use, so clients can hide actions you aren't allowed to perform.
+/// It is computed from the same access rules the endpoints enforce.
+#[utoipa::path(
+    get,
+    path = "/auth/capabilities",
+    responses(
+        (status = 200, description = "The endpoints you are allowed to use", body = AllowedEndpoints),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Misc"
+)]
+async fn get_auth_capabilities(ExtractUser(user): ExtractUser) -> Json<AllowedEndpoints> {
+    Json(AllowedEndpoints {
+        endpoints: allowed_endpoints(&user),
+    })
+}
+// endregion: get_auth_capabilities
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new()
+        .route("/auth/capabilities", get(get_auth_capabilities))
+        .route("/auth/check", get(get_auth_check))
+}
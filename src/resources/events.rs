@@ -0,0 +1,71 @@
+use crate::api::security::authentication::ExtractUser;
+use crate::AppState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::{routing::get, Router};
+use futures::stream;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Subscribe to real-time events.
+// region: get_events
+/// This endpoint streams events like received friend requests as server-sent events.
+/// It is an alternative to polling your notifications, keep-alive comments are sent periodically to hold the connection open.
+/// The amount of simultaneous connections per user is limited depending on their permission level.
+#[utoipa::path(
+    get,
+    path = "/events",
+    responses(
+        (status = 200, description = "Stream of events", body = ServerEvent, content_type = "text/event-stream"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 429, description = "Too many open connections"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Notification"
+)]
+async fn get_events(ExtractUser(user): ExtractUser, State(state): State<AppState>) -> Response {
+    let Some(guard) = state.events.connect(
+        &user.key,
+        state
+            .config
+            .max_connections
+            .for_level(&user.permission_level),
+    ) else {
+        return (StatusCode::TOO_MANY_REQUESTS, "Too many open connections").into_response();
+    };
+    let receiver = state.events.subscribe(&user.key);
+
+    // The guard lives inside the stream, so the connection is released as soon as the stream is dropped
+    let stream = stream::unfold((receiver, guard), |(mut receiver, guard)| async move {
+        loop {
+            let received = tokio::select! {
+                received = receiver.recv() => received,
+                _ = guard.closed() => return None,
+            };
+            match received {
+                Ok(event) => {
+                    let event = Event::default()
+                        .json_data(event)
+                        .unwrap_or_else(|_| Event::default().comment("Failed to serialize event"));
+                    return Some((Ok::<Event, Infallible>(event), (receiver, guard)));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+// endregion: get_events
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new().route("/events", get(get_events))
+}
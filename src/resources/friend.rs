@@ -1,27 +1,57 @@
+use crate::api::database::db::is_duplicate_key_error;
 use crate::api::entities::friendship::{
-    are_friends, find_friendship_by_keys, remove_friendship_by_id, Friendship,
+    are_friends, count_friends_of_friends, count_friendships_since, find_friend_keys,
+    find_friendship_by_keys, remove_friendship_by_id, Friendship,
 };
 use crate::api::entities::notification::Notification;
-use crate::api::entities::user::find_user_by_name;
-use crate::api::models::query_models::{IncludeUserProfile, PaginationQuery, UserName};
+use crate::api::entities::user::{
+    count_sent_friend_requests, find_suggestable_users, find_user_by_name, find_users_by_names,
+    find_users_joined_between, remove_mutual_favorites, FriendRequestSelection, User,
+};
+use crate::api::models::body_models::FriendRequestBatch;
+use crate::api::models::event_models::{
+    FriendRequestAcceptedEvent, FriendRequestReceivedEvent, ServerEvent,
+};
+use crate::api::models::query_models::{
+    FavoritesFirst, FieldSelection, FriendRequestFilter, IncludeUserProfile, PaginationQuery,
+    UserName,
+};
+use crate::api::models::response_models::{
+    FriendCount, FriendRequestBatchOutcome, FriendRequestBatchResult, FriendRequestSummary,
+    Pagination, UserList, UserPublicInformation,
+};
 use crate::api::security::authentication::ExtractUser;
-use crate::api::utils::time_operations::timestamp_now_nanos;
-use crate::{unpack_result, unpack_result_option, AppState};
+use crate::api::utils::fields::select_fields;
+use crate::api::utils::json_body::JsonBody;
+use crate::api::utils::limits::MAX_SUGGESTION_FAN_OUT;
+use crate::api::utils::ndjson::{accepts_ndjson, ndjson_response};
+use crate::api::utils::time_operations::{rfc3339_to_nanos, timestamp_now_nanos};
+use crate::{
+    unpack_option, unpack_result, unpack_result_bad_request, unpack_result_conflict,
+    unpack_result_option, AppState,
+};
 use axum::extract::{Query, State};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::routing::{delete, post};
 use axum::{routing::get, Json, Router};
+use axum_valid::Valid;
+use std::collections::HashMap;
 
 /// Retrieve your current friends.
 // region: get_friend
 /// This endpoint returns a list of users that have sent you friend requests.
+/// Send `Accept: application/x-ndjson` to receive the page as newline delimited JSON instead, one friend per line in no particular order.
+/// The friends are then streamed as they are read from the database and the total amount of friends is sent in the X-Total-Count header.
 #[utoipa::path(
     get,
     path = "/friend",
     params(PaginationQuery, IncludeUserProfile),
     responses(
-        (status = 200, description = "Your friends", body = FriendList),
+        (status = 200, description = "Your friends", content(
+            ("application/json" = FriendList),
+            ("application/x-ndjson" = FriendInformation)
+        )),
         (status = 401, description = "Invalid API Key"),
         (status = 500, description = "Server error"),
     ),
@@ -33,12 +63,31 @@ use axum::{routing::get, Json, Router};
 async fn get_friend(
     ExtractUser(user): ExtractUser,
     State(state): State<AppState>,
+    headers: HeaderMap,
     pagination: Query<PaginationQuery>,
     profile_query: Query<IncludeUserProfile>,
 ) -> Response {
-    let pagination = pagination.sanitize();
+    let pagination = pagination.sanitize(state.config);
     let page = pagination.page.unwrap_or(1);
-    let page_size = pagination.page_size.unwrap_or(10);
+    let page_size = pagination
+        .page_size
+        .unwrap_or(state.config.default_page_size);
+
+    if accepts_ndjson(&headers) {
+        let (friends, total) = unpack_result!(
+            user.friend_stream_with_pagination(
+                &state.database.user_collection,
+                &state.database.friendship_collection,
+                page,
+                page_size,
+                profile_query.include_user_profile,
+                state.config.online_threshold_nanos()
+            )
+            .await,
+            "An error occured while fetching your friendships"
+        );
+        return ndjson_response(friends, total);
+    }
 
     let friend_list = unpack_result!(
         user.friend_list_with_pagination(
@@ -46,7 +95,8 @@ async fn get_friend(
             &state.database.friendship_collection,
             page,
             page_size,
-            profile_query.include_user_profile
+            profile_query.include_user_profile,
+            state.config.online_threshold_nanos()
         )
         .await,
         "An error occured while fetching your friendships"
@@ -56,6 +106,372 @@ async fn get_friend(
 }
 // endregion: get_friend
 
+/// Retrieve your friends with their presence.
+// region: get_friend_detailed
+/// This endpoint returns your friends together with whether they are currently online and when they were last seen.
+/// The presence of friends who set their online date to private is not included.
+/// Your favorite friends can optionally be listed first.
+#[utoipa::path(
+    get,
+    path = "/friend/detailed",
+    params(PaginationQuery, FavoritesFirst),
+    responses(
+        (status = 200, description = "Your friends and their presence", body = FriendPresenceList),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn get_friend_detailed(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    pagination: Query<PaginationQuery>,
+    order: Query<FavoritesFirst>,
+) -> Response {
+    let pagination = pagination.sanitize(state.config);
+    let page = pagination.page.unwrap_or(1);
+    let page_size = pagination
+        .page_size
+        .unwrap_or(state.config.default_page_size);
+
+    let friend_list = unpack_result!(
+        user.friend_presence_list_with_pagination(
+            &state.database.user_collection,
+            &state.database.friendship_collection,
+            page,
+            page_size,
+            order.favorites_first,
+            state.config.online_threshold_nanos(),
+        )
+        .await,
+        "An error occured while fetching your friendships"
+    );
+
+    Json(friend_list).into_response()
+}
+// endregion: get_friend_detailed
+
+/// Retrieve your friend count.
+// region: get_friend_count
+/// This endpoint returns how many friends and pending friend requests you have without listing them.
+#[utoipa::path(
+    get,
+    path = "/friend/count",
+    responses(
+        (status = 200, description = "Your friend and friend request counts", body = FriendCount),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn get_friend_count(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+) -> Response {
+    let friends = unpack_result!(
+        user.friend_count(
+            &state.database.user_collection,
+            &state.database.friendship_collection
+        )
+        .await,
+        "An error occured while counting your friends"
+    );
+
+    let pending_sent = unpack_result!(
+        count_sent_friend_requests(&state.database.user_collection, &user.key).await,
+        "An error occured while counting your friend requests"
+    );
+
+    Json(FriendCount {
+        friends,
+        pending_received: user.friend_requests.len() as u64,
+        pending_sent,
+    })
+    .into_response()
+}
+// endregion: get_friend_count
+
+/// Mark a friend as favorite.
+// region: post_friend_favorite
+/// This endpoint allows you to mark one of your friends as favorite, favorites can be listed first in your detailed friend list.
+#[utoipa::path(
+    post,
+    path = "/friend/favorite",
+    params(UserName),
+    responses(
+        (status = 200, description = "Friend was marked as favorite"),
+        (status = 400, description = "Not friends with the user, already a favorite or username contains invalid characters"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn post_friend_favorite(
+    ExtractUser(mut user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserName>,
+) -> Response {
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
+
+    let target = unpack_result_option!(
+        find_user_by_name(&state.database.user_collection, &query.name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occurred while fetching user"
+    );
+
+    let friends = unpack_result!(
+        are_friends(
+            &state.database.friendship_collection,
+            vec![user.key.clone(), target.key.clone()],
+        )
+        .await,
+        "An error occurred while fetching friendship"
+    );
+
+    if let Err(message) = user.add_favorite(&target.key, friends) {
+        return Json((StatusCode::BAD_REQUEST, message)).into_response();
+    }
+
+    unpack_result!(
+        user.save(&state.database.user_collection).await,
+        "An error occured while saving the user"
+    );
+
+    Json((StatusCode::OK, "Friend marked as favorite")).into_response()
+}
+// endregion: post_friend_favorite
+
+/// Unmark a favorite friend.
+// region: delete_friend_favorite
+/// This endpoint allows you to remove a friend from your favorites.
+#[utoipa::path(
+    delete,
+    path = "/friend/favorite",
+    params(UserName),
+    responses(
+        (status = 200, description = "Friend was removed from your favorites"),
+        (status = 400, description = "User is not a favorite or username contains invalid characters"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn delete_friend_favorite(
+    ExtractUser(mut user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserName>,
+) -> Response {
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
+
+    let target = unpack_result_option!(
+        find_user_by_name(&state.database.user_collection, &query.name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occurred while fetching user"
+    );
+
+    if !user.favorite_keys.remove(&target.key) {
+        return Json((StatusCode::BAD_REQUEST, "User is not a favorite")).into_response();
+    }
+
+    unpack_result!(
+        user.save(&state.database.user_collection).await,
+        "An error occured while saving the user"
+    );
+
+    Json((StatusCode::OK, "Friend removed from favorites")).into_response()
+}
+// endregion: delete_friend_favorite
+
+/// Retrieve friend suggestions.
+// region: get_friend_suggestions
+/// This endpoint suggests users you aren't friends with yet, sorted by how many friends you have in common.
+/// Only users which can be found via search, accept friend requests and don't restrict discovery to their friends are suggested.
+#[utoipa::path(
+    get,
+    path = "/friend/suggestions",
+    params(PaginationQuery, IncludeUserProfile, FieldSelection),
+    responses(
+        (status = 200, description = "Suggested users", body = UserList),
+        (status = 400, description = "Unknown fields requested"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn get_friend_suggestions(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    pagination: Query<PaginationQuery>,
+    profile_query: Query<IncludeUserProfile>,
+    fields: Query<FieldSelection>,
+) -> Response {
+    let pagination = pagination.sanitize(state.config);
+    let page = pagination.page.unwrap_or(1);
+    let page_size = pagination
+        .page_size
+        .unwrap_or(state.config.default_page_size);
+
+    let friend_keys = unpack_result!(
+        find_friend_keys(&state.database.friendship_collection, &user.key).await,
+        "An error occured while fetching your friendships"
+    );
+
+    let mutual_counts = unpack_result!(
+        count_friends_of_friends(
+            &state.database.friendship_collection,
+            &user.key,
+            &friend_keys,
+            MAX_SUGGESTION_FAN_OUT
+        )
+        .await,
+        "An error occured while fetching friend suggestions"
+    );
+
+    let candidate_keys = mutual_counts
+        .keys()
+        .filter(|key| {
+            !user.block_list.contains_key(*key) && !user.friend_requests.contains_key(*key)
+        })
+        .cloned()
+        .collect();
+
+    let mut candidates = unpack_result!(
+        find_suggestable_users(&state.database.user_collection, candidate_keys, &user.key).await,
+        "An error occured while fetching friend suggestions"
+    );
+    candidates.sort_by(|a, b| {
+        mutual_counts[&b.key]
+            .cmp(&mutual_counts[&a.key])
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    let total = candidates.len() as u32;
+    let users: Vec<UserPublicInformation> = candidates
+        .iter()
+        .skip(((page - 1) * page_size) as usize)
+        .take(page_size as usize)
+        .map(|target| {
+            let mut information = target.public_information(
+                false,
+                profile_query.include_user_profile,
+                &user.timezone,
+                state.config.online_threshold_nanos(),
+            );
+            if target.settings.show_mutual_friends.is_visible(false) {
+                information.mutual_friends_count = Some(mutual_counts[&target.key]);
+            }
+            information
+        })
+        .collect();
+
+    let pagination = Pagination::new(total, page, page_size, users.len() as u32);
+    let user_list = unpack_result_bad_request!(select_fields::<UserPublicInformation>(
+        &UserList { users, pagination },
+        &fields,
+        Some("users")
+    ));
+    Json(user_list).into_response()
+}
+// endregion: get_friend_suggestions
+
+/// Retrieve friends who joined around the same time as you.
+// region: get_friend_cohort
+/// This endpoint returns your friends who created their account close to when you created yours, oldest first.
+/// The window is configured by the server, friends who set their join date to private are not included.
+#[utoipa::path(
+    get,
+    path = "/friend/cohort",
+    params(PaginationQuery, IncludeUserProfile, FieldSelection),
+    responses(
+        (status = 200, description = "Your friends who joined around the same time as you", body = UserList),
+        (status = 400, description = "Unknown fields requested"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn get_friend_cohort(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    pagination: Query<PaginationQuery>,
+    profile_query: Query<IncludeUserProfile>,
+    fields: Query<FieldSelection>,
+) -> Response {
+    let pagination = pagination.sanitize(state.config);
+    let page = pagination.page.unwrap_or(1);
+    let page_size = pagination
+        .page_size
+        .unwrap_or(state.config.default_page_size);
+
+    let friend_keys = unpack_result!(
+        find_friend_keys(&state.database.friendship_collection, &user.key).await,
+        "An error occured while fetching your friendships"
+    );
+
+    let window = state.config.cohort_window_nanos();
+    let cohort = unpack_result!(
+        find_users_joined_between(
+            &state.database.user_collection,
+            friend_keys.into_iter().collect(),
+            user.created_stamp.saturating_sub(window),
+            user.created_stamp.saturating_add(window)
+        )
+        .await,
+        "An error occured while fetching your friends"
+    );
+
+    let cohort: Vec<_> = cohort
+        .iter()
+        .filter(|friend| friend.settings.show_join_date.is_visible(true))
+        .collect();
+
+    let total = cohort.len() as u32;
+    let users: Vec<UserPublicInformation> = cohort
+        .iter()
+        .skip(((page - 1) * page_size) as usize)
+        .take(page_size as usize)
+        .map(|friend| {
+            friend.public_information(
+                true,
+                profile_query.include_user_profile,
+                &user.timezone,
+                state.config.online_threshold_nanos(),
+            )
+        })
+        .collect();
+
+    let pagination = Pagination::new(total, page, page_size, users.len() as u32);
+    let user_list = unpack_result_bad_request!(select_fields::<UserPublicInformation>(
+        &UserList { users, pagination },
+        &fields,
+        Some("users")
+    ));
+    Json(user_list).into_response()
+}
+// endregion: get_friend_cohort
+
 /// Remove a friend.
 // region: delete_friend
 /// This endpoint allows the user to remove a friend..
@@ -65,7 +481,7 @@ async fn get_friend(
     params(UserName),
     responses(
         (status = 200, description = "Friend successfully removed"),
-        (status = 400, description = "Unable to remove friend"),
+        (status = 400, description = "Unable to remove friend or username contains invalid characters"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Server error"),
@@ -80,7 +496,7 @@ async fn delete_friend(
     State(state): State<AppState>,
     query: Query<UserName>,
 ) -> Response {
-    let query = query.sanitize();
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
 
     let target = unpack_result_option!(
         find_user_by_name(&state.database.user_collection, &query.name).await,
@@ -92,11 +508,11 @@ async fn delete_friend(
     let friendship = unpack_result_option!(
         find_friendship_by_keys(
             &state.database.friendship_collection,
-            vec![target.key, user.key],
+            vec![target.key.clone(), user.key.clone()],
         )
         .await,
         StatusCode::BAD_REQUEST,
-        "Not friend with the user",
+        "Not friends with the user",
         "An error occured while fetching friendship"
     );
 
@@ -109,6 +525,11 @@ async fn delete_friend(
         "An error occured while removing friendship"
     );
 
+    unpack_result!(
+        remove_mutual_favorites(&state.database.user_collection, &user.key, &target.key).await,
+        "An error occured while removing favorites"
+    );
+
     Json((StatusCode::OK, "Friend successfully removed")).into_response()
 }
 // endregion: delete_friend
@@ -116,12 +537,14 @@ async fn delete_friend(
 /// Retrieve pending friend requests.
 // region: get_friend_request
 /// This endpoint returns a list of users that have sent you friend requests.
+/// The requests can be sorted by the date they were sent and filtered to only include recent ones.
 #[utoipa::path(
     get,
     path = "/friend/request",
-    params(PaginationQuery, IncludeUserProfile),
+    params(PaginationQuery, IncludeUserProfile, FriendRequestFilter),
     responses(
         (status = 200, description = "Users you have pending friend requests from", body = FriendRequests),
+        (status = 400, description = "Invalid since date"),
         (status = 401, description = "Invalid API Key"),
         (status = 500, description = "Server error"),
     ),
@@ -135,17 +558,34 @@ async fn get_friend_request(
     State(state): State<AppState>,
     pagination: Query<PaginationQuery>,
     profile_query: Query<IncludeUserProfile>,
+    filter: Query<FriendRequestFilter>,
 ) -> Response {
-    let pagination = pagination.sanitize();
+    let pagination = pagination.sanitize(state.config);
     let page = pagination.page.unwrap_or(1);
-    let page_size = pagination.page_size.unwrap_or(10);
+    let page_size = pagination
+        .page_size
+        .unwrap_or(state.config.default_page_size);
+
+    let since = match &filter.since {
+        Some(since) => Some(unpack_option!(
+            rfc3339_to_nanos(since),
+            StatusCode::BAD_REQUEST,
+            "Invalid since date, expected RFC 3339 format"
+        )),
+        None => None,
+    };
 
     let requests = unpack_result!(
         user.friend_requests_with_pagination(
             &state.database.user_collection,
             page,
             page_size,
-            profile_query.include_user_profile
+            profile_query.include_user_profile,
+            FriendRequestSelection {
+                sort: filter.sort,
+                since
+            },
+            state.config.online_threshold_nanos()
         )
         .await,
         "An error occured while fetching your friend requests"
@@ -157,15 +597,20 @@ async fn get_friend_request(
 /// Send friend requests.
 // region: post_friend_request
 /// This endpoint allows you to send a friend request to users.
+/// If you only accept mutual requests and the user already sent you a request, you become friends immediately.
+/// Users can choose to silently drop incoming requests, in which case the request is reported as sent regardless.
+/// Depending on the server configuration, requests to users who don't allow friend requests are either rejected or silently queued until they allow them again.
 #[utoipa::path(
     post,
     path = "/friend/request",
     params(UserName),
     responses(
-        (status = 200, description = "Friend request was sent"),
-        (status = 400, description = "Unable to send request"),
+        (status = 200, description = "Friend request was sent or friendship was created"),
+        (status = 400, description = "Unable to send request or username contains invalid characters"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "User not found"),
+        (status = 409, description = "User can't receive any more friend requests"),
+        (status = 429, description = "You made too many new friends recently, try again later"),
         (status = 500, description = "Server error"),
     ),
     security(
@@ -174,11 +619,11 @@ async fn get_friend_request(
     tag = "Friends"
 )]
 async fn post_friend_request(
-    ExtractUser(user): ExtractUser,
+    ExtractUser(mut user): ExtractUser,
     State(state): State<AppState>,
     query: Query<UserName>,
 ) -> Response {
-    let query = query.sanitize();
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
 
     let mut target = unpack_result_option!(
         find_user_by_name(&state.database.user_collection, &query.name).await,
@@ -191,7 +636,7 @@ async fn post_friend_request(
         return (StatusCode::NOT_FOUND, "User not found").into_response();
     }
 
-    if target.key == user.key {
+    if user.is_self(&target) {
         return Json((
             StatusCode::BAD_REQUEST,
             "Can't send a friend request to yourself",
@@ -199,7 +644,8 @@ async fn post_friend_request(
         .into_response();
     }
 
-    if !target.settings.allow_friend_requests {
+    let delivery = request_delivery(&target, state.config.queue_private_friend_requests);
+    if delivery == RequestDelivery::Reject {
         return Json((
             StatusCode::BAD_REQUEST,
             "User does not allow friend requests",
@@ -224,7 +670,9 @@ async fn post_friend_request(
         .into_response();
     }
 
-    if target.friend_requests.contains_key(&user.key) {
+    if target.friend_requests.contains_key(&user.key)
+        || target.queued_friend_requests.contains_key(&user.key)
+    {
         return Json((
             StatusCode::BAD_REQUEST,
             "Already sent a request to the user",
@@ -232,6 +680,70 @@ async fn post_friend_request(
         .into_response();
     }
 
+    let max_pending_requests = state
+        .config
+        .max_pending_friend_requests
+        .for_level(&target.permission_level);
+
+    if delivery == RequestDelivery::Queue {
+        if target.settings.reveal_request_received
+            && target.friend_requests.len() + target.queued_friend_requests.len()
+                < max_pending_requests
+        {
+            target
+                .queued_friend_requests
+                .insert(user.key.clone(), timestamp_now_nanos());
+
+            unpack_result!(
+                target.save(&state.database.user_collection).await,
+                "An error occured while saving the target user"
+            );
+        }
+        return Json((StatusCode::OK, "Friend request sent")).into_response();
+    }
+
+    if user.settings.mutual_requests_only && user.friend_requests.contains_key(&target.key) {
+        if let Err(e) = check_friendship_window(&state, &user).await {
+            return e.into_response();
+        }
+
+        user.friend_requests.remove(&target.key);
+        unpack_result!(
+            user.save(&state.database.user_collection).await,
+            "An error occured while saving the user"
+        );
+
+        let new_friendship = Friendship::new(vec![user.key.clone(), target.key.clone()]);
+        unpack_result_conflict!(
+            new_friendship
+                .save(&state.database.friendship_collection)
+                .await,
+            "You are already friends with the user",
+            "An error occured while saving the friendship"
+        );
+
+        state.events.notify(
+            &target,
+            ServerEvent::FriendRequestAccepted(FriendRequestAcceptedEvent {
+                accepter_name: user.name.clone(),
+            }),
+        );
+
+        return (StatusCode::OK, "Friendship created").into_response();
+    }
+
+    if !target.settings.reveal_request_received {
+        return Json((StatusCode::OK, "Friend request sent")).into_response();
+    }
+
+    if target.friend_requests.len() + target.queued_friend_requests.len() >= max_pending_requests {
+        return (
+            StatusCode::CONFLICT,
+            "User can't receive any more friend requests",
+        )
+            .into_response();
+    }
+
     target
         .friend_requests
         .insert(user.key.clone(), timestamp_now_nanos());
@@ -251,6 +763,13 @@ async fn post_friend_request(
         "An error occured while saving notification"
     );
 
+    state.events.notify(
+        &target,
+        ServerEvent::FriendRequestReceived(FriendRequestReceivedEvent {
+            sender_name: user.name.clone(),
+        }),
+    );
+
     Json((StatusCode::OK, "Friend request sent")).into_response()
 }
 // endregion: post_friend_request
@@ -264,7 +783,7 @@ async fn post_friend_request(
     params(UserName),
     responses(
         (status = 200, description = "Friend request was retracted"),
-        (status = 400, description = "You did not send a request to the user"),
+        (status = 400, description = "You did not send a request to the user or username contains invalid characters"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Server error"),
@@ -279,7 +798,7 @@ async fn delete_friend_request(
     State(state): State<AppState>,
     query: Query<UserName>,
 ) -> Response {
-    let query = query.sanitize();
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
 
     let mut target = unpack_result_option!(
         find_user_by_name(&state.database.user_collection, &query.name).await,
@@ -288,7 +807,9 @@ async fn delete_friend_request(
         "An error occurred while fetching user"
     );
 
-    if !target.friend_requests.contains_key(&user.key) {
+    if target.friend_requests.remove(&user.key).is_none()
+        && target.queued_friend_requests.remove(&user.key).is_none()
+    {
         return Json((
             StatusCode::BAD_REQUEST,
             "You did not send a request to the user",
@@ -296,8 +817,6 @@ async fn delete_friend_request(
         .into_response();
     }
 
-    target.friend_requests.remove(&user.key);
-
     unpack_result!(
         target.save(&state.database.user_collection).await,
         "An error occured while saving the target user"
@@ -307,6 +826,39 @@ async fn delete_friend_request(
 }
 // endregion: delete_friend_request
 
+/// Retrieve your pending friend request counts.
+// region: get_friend_request_summary
+/// This endpoint returns how many friend requests you received and sent which weren't answered yet, without listing them.
+#[utoipa::path(
+    get,
+    path = "/friend/request/summary",
+    responses(
+        (status = 200, description = "Your pending friend request counts", body = FriendRequestSummary),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn get_friend_request_summary(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+) -> Response {
+    let outgoing = unpack_result!(
+        count_sent_friend_requests(&state.database.user_collection, &user.key).await,
+        "An error occured while counting your friend requests"
+    );
+
+    Json(FriendRequestSummary {
+        incoming: user.friend_requests.len() as u64,
+        outgoing,
+    })
+    .into_response()
+}
+// endregion: get_friend_request_summary
+
 /// Accept a pending friend request.
 // region: post_friend_request_accept
 /// This endpoint allows you to accept friend requests.
@@ -316,9 +868,12 @@ async fn delete_friend_request(
     params(UserName),
     responses(
         (status = 200, description = "Friend request accepted"),
+        (status = 400, description = "You only accept mutual requests or username contains invalid characters"),
         (status = 401, description = "Unable to accept request"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "User not found or no pending request from user"),
+        (status = 409, description = "You are already friends with the user"),
+        (status = 429, description = "You made too many new friends recently"),
         (status = 500, description = "Server error"),
     ),
     security(
@@ -331,7 +886,7 @@ async fn post_friend_request_accept(
     State(state): State<AppState>,
     query: Query<UserName>,
 ) -> Response {
-    let query = query.sanitize();
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
 
     let target = unpack_result_option!(
         find_user_by_name(&state.database.user_collection, &query.name).await,
@@ -340,54 +895,10 @@ async fn post_friend_request_accept(
         "An error occurred while fetching user"
     );
 
-    if !user.friend_requests.contains_key(&target.key) {
-        return (
-            StatusCode::NOT_FOUND,
-            "User not found or no pending request from user",
-        )
-            .into_response();
-    };
-
-    user.friend_requests.remove(&target.key);
-    unpack_result!(
-        user.save(&state.database.user_collection).await,
-        "An error occured while saving the user"
-    );
-
-    if target.block_list.contains_key(&user.key) || user.block_list.contains_key(&target.key) {
-        return (
-            StatusCode::NOT_FOUND,
-            "User not found or no pending request from user",
-        )
-            .into_response();
-    };
-
-    let already_friends = unpack_result!(
-        are_friends(
-            &state.database.friendship_collection,
-            vec![user.key.clone(), target.key.clone()],
-        )
-        .await,
-        "An error occured while trying to fetch friendship"
-    );
-
-    if already_friends {
-        return (
-            StatusCode::BAD_REQUEST,
-            "You are already friends with the user",
-        )
-            .into_response();
+    match accept_friend_request(&state, &mut user, &target).await {
+        Ok(()) => (StatusCode::OK, "Friend request accepted").into_response(),
+        Err(e) => e.into_response(),
     }
-
-    let new_friendship = Friendship::new(vec![user.key, target.key]);
-    unpack_result!(
-        new_friendship
-            .save(&state.database.friendship_collection)
-            .await,
-        "An error occured while saving the friendship"
-    );
-
-    (StatusCode::OK, "Friend request accepted").into_response()
 }
 // endregion: post_friend_request_accept
 
@@ -400,6 +911,7 @@ async fn post_friend_request_accept(
     params(UserName),
     responses(
         (status = 200, description = "Friend request denied"),
+        (status = 400, description = "Username contains invalid characters"),
         (status = 401, description = "Unable to deny request"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "User not found or no pending request from user"),
@@ -415,7 +927,7 @@ async fn post_friend_request_deny(
     State(state): State<AppState>,
     query: Query<UserName>,
 ) -> Response {
-    let query = query.sanitize();
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
 
     let target = unpack_result_option!(
         find_user_by_name(&state.database.user_collection, &query.name).await,
@@ -424,31 +936,382 @@ async fn post_friend_request_deny(
         "An error occurred while fetching user"
     );
 
+    match deny_friend_request(&state, &mut user, &target).await {
+        Ok(()) => (StatusCode::OK, "Friend request denied").into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+// endregion: post_friend_request_deny
+
+/// Accept multiple pending friend requests.
+// region: post_friend_request_accept_bulk
+/// This endpoint allows you to accept up to 50 friend requests at once.
+/// Every request is handled like with POST /friend/request/accept, the outcome is reported per username.
+#[utoipa::path(
+    post,
+    path = "/friend/request/accept/bulk",
+    request_body = FriendRequestBatch,
+    responses(
+        (status = 200, description = "The outcome for every username", body = FriendRequestBatchResult),
+        (status = 400, description = "Invalid list of usernames"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn post_friend_request_accept_bulk(
+    ExtractUser(mut user): ExtractUser,
+    State(state): State<AppState>,
+    Valid(JsonBody(body)): Valid<JsonBody<FriendRequestBatch>>,
+) -> Response {
+    let (targets, mut results) = unpack_result!(
+        find_batch_targets(&state, &body.names).await,
+        "An error occured while fetching users"
+    );
+
+    for target in targets {
+        results.push(
+            match accept_friend_request(&state, &mut user, &target).await {
+                Ok(()) => batch_outcome(target.name, StatusCode::OK, "Friend request accepted"),
+                Err(e) => batch_outcome(target.name, e.status(), e.message()),
+            },
+        );
+    }
+
+    Json(FriendRequestBatchResult { results }).into_response()
+}
+// endregion: post_friend_request_accept_bulk
+
+/// Deny multiple pending friend requests.
+// region: post_friend_request_deny_bulk
+/// This endpoint allows you to deny up to 50 friend requests at once.
+/// Every request is handled like with POST /friend/request/deny, the outcome is reported per username.
+#[utoipa::path(
+    post,
+    path = "/friend/request/deny/bulk",
+    request_body = FriendRequestBatch,
+    responses(
+        (status = 200, description = "The outcome for every username", body = FriendRequestBatchResult),
+        (status = 400, description = "Invalid list of usernames"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Friends"
+)]
+async fn post_friend_request_deny_bulk(
+    ExtractUser(mut user): ExtractUser,
+    State(state): State<AppState>,
+    Valid(JsonBody(body)): Valid<JsonBody<FriendRequestBatch>>,
+) -> Response {
+    let (targets, mut results) = unpack_result!(
+        find_batch_targets(&state, &body.names).await,
+        "An error occured while fetching users"
+    );
+
+    for target in targets {
+        results.push(
+            match deny_friend_request(&state, &mut user, &target).await {
+                Ok(()) => batch_outcome(target.name, StatusCode::OK, "Friend request denied"),
+                Err(e) => batch_outcome(target.name, e.status(), e.message()),
+            },
+        );
+    }
+
+    Json(FriendRequestBatchResult { results }).into_response()
+}
+// endregion: post_friend_request_deny_bulk
+
+/// What happens to a friend request sent to the user
+#[derive(Debug, PartialEq)]
+enum RequestDelivery {
+    Deliver,
+    /// Stored silently until the user allows friend requests again
+    Queue,
+    Reject,
+}
+
+/// Requests to users who don't allow friend requests are rejected unless the server is configured to queue them
+fn request_delivery(target: &User, queue_private_friend_requests: bool) -> RequestDelivery {
+    if target.settings.allow_friend_requests {
+        RequestDelivery::Deliver
+    } else if queue_private_friend_requests {
+        RequestDelivery::Queue
+    } else {
+        RequestDelivery::Reject
+    }
+}
+
+/// Why a pending friend request couldn't be answered
+enum AnswerError {
+    NotPending,
+    MutualRequestsOnly,
+    TooManyFriendships,
+    AlreadyFriends,
+    Conflict,
+    Database(&'static str),
+}
+
+impl AnswerError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AnswerError::NotPending => StatusCode::NOT_FOUND,
+            AnswerError::MutualRequestsOnly | AnswerError::AlreadyFriends => {
+                StatusCode::BAD_REQUEST
+            }
+            AnswerError::TooManyFriendships => StatusCode::TOO_MANY_REQUESTS,
+            AnswerError::Conflict => StatusCode::CONFLICT,
+            AnswerError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        match self {
+            AnswerError::NotPending => "User not found or no pending request from user",
+            AnswerError::MutualRequestsOnly => {
+                "You only accept mutual requests, send a friend request to the user instead"
+            }
+            AnswerError::TooManyFriendships => {
+                "You made too many new friends recently, try again later"
+            }
+            AnswerError::AlreadyFriends | AnswerError::Conflict => {
+                "You are already friends with the user"
+            }
+            AnswerError::Database(message) => message,
+        }
+    }
+}
+
+impl IntoResponse for AnswerError {
+    fn into_response(self) -> Response {
+        match self {
+            // Responds the same way as the unpack macros
+            AnswerError::Conflict | AnswerError::Database(_) => {
+                Json((self.status(), self.message())).into_response()
+            }
+            _ => (self.status(), self.message()).into_response(),
+        }
+    }
+}
+
+fn batch_outcome(name: String, status: StatusCode, message: &str) -> FriendRequestBatchOutcome {
+    FriendRequestBatchOutcome {
+        name,
+        status: status.as_u16(),
+        message: message.to_string(),
+    }
+}
+
+/// Every path which creates a friendship has to pass this check first
+async fn check_friendship_window(state: &AppState, user: &User) -> Result<(), AnswerError> {
+    let since = timestamp_now_nanos().saturating_sub(state.config.friendship_window_nanos());
+    let recent_friendships =
+        count_friendships_since(&state.database.friendship_collection, &user.key, since)
+            .await
+            .map_err(|_| {
+                AnswerError::Database("An error occured while counting your friendships")
+            })?;
+    if !user.can_form_friendship(recent_friendships, state.config.max_friendships_per_window) {
+        return Err(AnswerError::TooManyFriendships);
+    }
+    Ok(())
+}
+
+async fn accept_friend_request(
+    state: &AppState,
+    user: &mut User,
+    target: &User,
+) -> Result<(), AnswerError> {
     if !user.friend_requests.contains_key(&target.key) {
-        return (
-            StatusCode::NOT_FOUND,
-            "User not found or no pending request from user",
-        )
-            .into_response();
+        return Err(AnswerError::NotPending);
     };
 
+    if user.settings.mutual_requests_only {
+        return Err(AnswerError::MutualRequestsOnly);
+    }
+
+    check_friendship_window(state, user).await?;
+
     user.friend_requests.remove(&target.key);
-    unpack_result!(
-        user.save(&state.database.user_collection).await,
-        "An error occured while saving the user"
+    user.save(&state.database.user_collection)
+        .await
+        .map_err(|_| AnswerError::Database("An error occured while saving the user"))?;
+
+    if target.block_list.contains_key(&user.key) || user.block_list.contains_key(&target.key) {
+        return Err(AnswerError::NotPending);
+    };
+
+    let already_friends = are_friends(
+        &state.database.friendship_collection,
+        vec![user.key.clone(), target.key.clone()],
+    )
+    .await
+    .map_err(|_| AnswerError::Database("An error occured while trying to fetch friendship"))?;
+
+    if already_friends {
+        return Err(AnswerError::AlreadyFriends);
+    }
+
+    let new_friendship = Friendship::new(vec![user.key.clone(), target.key.clone()]);
+    match new_friendship
+        .save(&state.database.friendship_collection)
+        .await
+    {
+        Ok(()) => {}
+        Err(e) if is_duplicate_key_error(&e) => return Err(AnswerError::Conflict),
+        Err(_) => {
+            return Err(AnswerError::Database(
+                "An error occured while saving the friendship",
+            ))
+        }
+    }
+
+    state.events.notify(
+        target,
+        ServerEvent::FriendRequestAccepted(FriendRequestAcceptedEvent {
+            accepter_name: user.name.clone(),
+        }),
     );
 
-    (StatusCode::OK, "Friend request denied").into_response()
+    Ok(())
+}
+
+async fn deny_friend_request(
+    state: &AppState,
+    user: &mut User,
+    target: &User,
+) -> Result<(), AnswerError> {
+    if !user.friend_requests.contains_key(&target.key) {
+        return Err(AnswerError::NotPending);
+    };
+
+    user.friend_requests.remove(&target.key);
+    user.save(&state.database.user_collection)
+        .await
+        .map_err(|_| AnswerError::Database("An error occured while saving the user"))?;
+
+    Ok(())
+}
+
+/// Looks up the users of a batch in the given order, usernames which are invalid or don't exist are reported right away
+async fn find_batch_targets(
+    state: &AppState,
+    names: &[String],
+) -> mongodb::error::Result<(Vec<User>, Vec<FriendRequestBatchOutcome>)> {
+    let mut outcomes = Vec::new();
+    let mut sanitized_names: Vec<String> = Vec::new();
+    for name in names {
+        match (UserName { name: name.clone() }).try_sanitize(state.config) {
+            Ok(query) if !query.name.is_empty() => {
+                let sanitized_name = query.name.to_lowercase();
+                if !sanitized_names.contains(&sanitized_name) {
+                    sanitized_names.push(sanitized_name);
+                }
+            }
+            Ok(_) => outcomes.push(batch_outcome(
+                name.clone(),
+                StatusCode::NOT_FOUND,
+                AnswerError::NotPending.message(),
+            )),
+            Err(e) => outcomes.push(batch_outcome(
+                name.clone(),
+                StatusCode::BAD_REQUEST,
+                &e.to_string(),
+            )),
+        }
+    }
+
+    let mut users: HashMap<String, User> =
+        find_users_by_names(&state.database.user_collection, &sanitized_names)
+            .await?
+            .into_iter()
+            .map(|user| (user.name.clone(), user))
+            .collect();
+
+    let mut targets = Vec::new();
+    for name in sanitized_names {
+        match users.remove(&name) {
+            Some(target) => targets.push(target),
+            None => outcomes.push(batch_outcome(
+                name,
+                StatusCode::NOT_FOUND,
+                AnswerError::NotPending.message(),
+            )),
+        }
+    }
+    Ok((targets, outcomes))
 }
-// endregion: post_friend_request_deny
 
 pub fn router() -> Router<AppState> {
     Router::<AppState>::new()
         .route("/friend", get(get_friend))
         .route("/friend", delete(delete_friend))
+        .route("/friend/cohort", get(get_friend_cohort))
+        .route("/friend/count", get(get_friend_count))
+        .route("/friend/detailed", get(get_friend_detailed))
+        .route("/friend/favorite", post(post_friend_favorite))
+        .route("/friend/favorite", delete(delete_friend_favorite))
         .route("/friend/request", get(get_friend_request))
         .route("/friend/request", post(post_friend_request))
         .route("/friend/request", delete(delete_friend_request))
         .route("/friend/request/accept", post(post_friend_request_accept))
+        .route(
+            "/friend/request/accept/bulk",
+            post(post_friend_request_accept_bulk),
+        )
         .route("/friend/request/deny", post(post_friend_request_deny))
+        .route(
+            "/friend/request/deny/bulk",
+            post(post_friend_request_deny_bulk),
+        )
+        .route("/friend/request/summary", get(get_friend_request_summary))
+        .route("/friend/suggestions", get(get_friend_suggestions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{self, doc};
+
+    fn user_allowing_requests(allow_friend_requests: bool) -> User {
+        let mut user: User = bson::from_document(doc! {
+            "key": "key",
+            "name": "name",
+            "display_name": "name",
+            "created_stamp": 0_i64,
+        })
+        .unwrap();
+        user.settings.allow_friend_requests = allow_friend_requests;
+        user
+    }
+
+    #[test]
+    fn requests_are_delivered_if_allowed() {
+        let target = user_allowing_requests(true);
+        assert_eq!(request_delivery(&target, false), RequestDelivery::Deliver);
+        assert_eq!(request_delivery(&target, true), RequestDelivery::Deliver);
+    }
+
+    #[test]
+    fn requests_to_private_users_are_rejected_by_default() {
+        let target = user_allowing_requests(false);
+        assert_eq!(request_delivery(&target, false), RequestDelivery::Reject);
+    }
+
+    #[test]
+    fn requests_to_private_users_are_queued_if_configured() {
+        let target = user_allowing_requests(false);
+        assert_eq!(request_delivery(&target, true), RequestDelivery::Queue);
+    }
+
+    #[test]
+    fn friendship_window_is_rejected_with_too_many_requests() {
+        let response = AnswerError::TooManyFriendships.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
 }
@@ -0,0 +1,86 @@
+use crate::api::middleware::ip_rate_limit::LimitByIp;
+use crate::api::models::response_models::{ServerCapabilities, ServerLimits};
+use crate::api::utils::limits::{
+    MAX_BIO_LENGTH, MAX_INTERESTS, MAX_INTEREST_LENGTH, MAX_MOOD_LENGTH, MAX_PRONOUNS_LENGTH,
+    MAX_STATUS_LENGTH,
+};
+use crate::config::Config;
+use crate::AppState;
+use axum::extract::State;
+use axum::http::header::CACHE_CONTROL;
+use axum::response::IntoResponse;
+use axum::{routing::get, Json, Router};
+
+/// Retrieve the server limits.
+// region: get_limits
+/// This endpoint returns the limits this server enforces, so clients can validate input before submitting it.
+/// It doesn't require an API key, instead it is rate limited by IP address.
+#[utoipa::path(
+    get,
+    path = "/limits",
+    responses(
+        (status = 200, description = "The limits of this server", body = ServerLimits),
+        (status = 429, description = "Too many requests from your IP address"),
+    ),
+    tag = "Misc"
+)]
+async fn get_limits(_: LimitByIp, State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(CACHE_CONTROL, "public, max-age=3600")],
+        Json(server_limits(state.config)),
+    )
+}
+// endregion: get_limits
+
+/// Retrieve the features of this server.
+// region: get_capabilities
+/// This endpoint returns which optional features this deployment supports and the limits it enforces, so clients can adapt their UI.
+/// It doesn't require an API key, instead it is rate limited by IP address.
+#[utoipa::path(
+    get,
+    path = "/capabilities",
+    responses(
+        (status = 200, description = "The features and limits of this server", body = ServerCapabilities),
+        (status = 429, description = "Too many requests from your IP address"),
+    ),
+    tag = "Misc"
+)]
+async fn get_capabilities(_: LimitByIp, State(state): State<AppState>) -> impl IntoResponse {
+    let capabilities = ServerCapabilities {
+        api_prefix: state.config.api_prefix.clone(),
+        event_stream: true,
+        websockets: false,
+        channels: false,
+        webhooks: false,
+        registration_open: false,
+        compression: state.config.compression_enabled,
+        limits: server_limits(state.config),
+    };
+
+    (
+        [(CACHE_CONTROL, "public, max-age=3600")],
+        Json(capabilities),
+    )
+}
+// endregion: get_capabilities
+
+fn server_limits(config: &Config) -> ServerLimits {
+    ServerLimits {
+        default_page_size: config.default_page_size,
+        max_page_size: config.max_page_size,
+        max_pronouns_length: MAX_PRONOUNS_LENGTH,
+        max_bio_length: MAX_BIO_LENGTH,
+        max_status_length: MAX_STATUS_LENGTH,
+        max_mood_length: MAX_MOOD_LENGTH,
+        max_interests: MAX_INTERESTS,
+        max_interest_length: MAX_INTEREST_LENGTH,
+        max_pending_friend_requests: config.max_pending_friend_requests.clone(),
+        max_connections: config.max_connections.clone(),
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new()
+        .route("/capabilities", get(get_capabilities))
+        .route("/limits", get(get_limits))
+}
@@ -35,9 +35,9 @@ async fn get_notification(
     State(state): State<AppState>,
     query: Query<PaginationQuery>,
 ) -> Response {
-    let query = query.sanitize();
+    let query = query.sanitize(state.config);
     let page = query.page.unwrap_or(1);
-    let page_size = query.page_size.unwrap_or(10);
+    let page_size = query.page_size.unwrap_or(state.config.default_page_size);
 
     let notifications = unpack_result!(
         find_notifications_by_receiver_key(&state.database.notification_collection, &user.key)
@@ -58,7 +58,7 @@ async fn get_notification(
     let end = std::cmp::min(start + page_size as usize, entry_count);
 
     let responses = stream::iter(notifications[start..end].iter())
-        .then(|notification| notification.get_response(&user, &state.database))
+        .then(|notification| notification.get_response(&user, &state.database, state.config))
         .try_collect::<Vec<_>>()
         .await
         .unwrap_or_else(|_| Vec::new());
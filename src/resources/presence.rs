@@ -0,0 +1,64 @@
+use crate::api::entities::user::record_presence_ping;
+use crate::api::models::query_models::PresencePingQuery;
+use crate::api::models::response_models::PresencePing;
+use crate::api::security::authentication::ExtractUserReadOnly;
+use crate::api::utils::time_operations::timestamp_now_nanos;
+use crate::{unpack_result, AppState};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{routing::post, Json, Router};
+use axum_valid::Valid;
+
+/// Keep yourself online.
+// region: post_presence_ping
+/// This endpoint marks you as online without counting as endpoint usage, meant to be called periodically by idle clients.
+/// It optionally sets your profile status. Pinging more than once every few seconds is rejected with 429.
+#[utoipa::path(
+    post,
+    path = "/presence/ping",
+    params(PresencePingQuery),
+    responses(
+        (status = 200, description = "You are online, includes how long you stay online without another request", body = PresencePing),
+        (status = 400, description = "Invalid status"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 429, description = "Pinged too often"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Misc"
+)]
+async fn post_presence_ping(
+    ExtractUserReadOnly(user): ExtractUserReadOnly,
+    State(state): State<AppState>,
+    query: Valid<Query<PresencePingQuery>>,
+) -> Response {
+    if state.recent_presence_pings.get(&user.key).is_some() {
+        return (StatusCode::TOO_MANY_REQUESTS, "Pinged too often").into_response();
+    }
+    state.recent_presence_pings.insert(&user.key, ());
+
+    let query = query.sanitize();
+    unpack_result!(
+        record_presence_ping(
+            &state.database.user_collection,
+            &user.key,
+            timestamp_now_nanos(),
+            query.status.as_deref()
+        )
+        .await,
+        "An error occured while updating your presence"
+    );
+
+    Json(PresencePing {
+        online_threshold_seconds: state.config.online_threshold_seconds,
+    })
+    .into_response()
+}
+// endregion: post_presence_ping
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new().route("/presence/ping", post(post_presence_ping))
+}
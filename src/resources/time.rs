@@ -0,0 +1,57 @@
+use crate::api::models::response_models::{ServerTime, TimeFormat};
+use crate::api::utils::time_operations::{nanos_to_date, nanos_to_rfc3339, timestamp_now_nanos};
+use crate::{api::security::authentication::ExtractUser, AppState};
+use axum::{routing::get, Json, Router};
+
+/// Retrieve the server time.
+// region: get_time
+/// This endpoint returns the current server time, which clients can use to calibrate their clock skew.
+/// It also lists the time formats used throughout the API, each with the current time as example.
+#[utoipa::path(
+    get,
+    path = "/time",
+    responses(
+        (status = 200, description = "The current server time and supported time formats", body = ServerTime),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Misc"
+)]
+async fn get_time(ExtractUser(user): ExtractUser) -> Json<ServerTime> {
+    let now = timestamp_now_nanos();
+    let rfc3339 = nanos_to_rfc3339(now);
+    let date = nanos_to_date(now, &user.timezone);
+
+    let supported_formats = vec![
+        TimeFormat {
+            name: "date".to_string(),
+            description: "Used for all dates in responses, in your timezone".to_string(),
+            example: date.clone(),
+        },
+        TimeFormat {
+            name: "rfc3339".to_string(),
+            description: "Accepted by parameters which filter by date, e.g. since".to_string(),
+            example: rfc3339.clone(),
+        },
+        TimeFormat {
+            name: "time_of_day".to_string(),
+            description: "HH:MM, used for the do not disturb window in your timezone".to_string(),
+            example: date.get(11..16).unwrap_or_default().to_string(),
+        },
+    ];
+
+    Json(ServerTime {
+        timestamp_nanos: now,
+        rfc3339,
+        date,
+        supported_formats,
+    })
+}
+// endregion: get_time
+
+pub fn router() -> Router<AppState> {
+    Router::<AppState>::new().route("/time", get(get_time))
+}
@@ -1,17 +1,40 @@
-use crate::api::entities::friendship::are_friends;
-use crate::api::entities::user::find_user_by_name;
+use crate::api::entities::friendship::{
+    are_friends, count_mutual_friends, find_friend_keys, find_friendships_by_key,
+    find_friendships_of_keys,
+};
+use crate::api::entities::notification::count_notifications_by_receiver_key;
+use crate::api::entities::user::{
+    claim_settings_change, count_sent_friend_requests, find_user_by_display_name,
+    find_user_by_name, find_users_by_names, find_users_with_keys, join_rank, User,
+};
+use crate::api::models::body_models::{BlockImport, PublicKeyUpload};
+use crate::api::models::enums::Capability;
 use crate::api::models::query_models::{
-    IncludeUserProfile, PaginationQuery, UserProfileEdit, UserSettingsEdit,
+    FieldSelection, IncludeUserProfile, OptionalUserName, PaginationQuery, SettingsReset,
+    UserProfileEdit, UserSettingsEdit,
 };
-use crate::api::models::user_settings::UserSettings;
-use crate::api::models::{query_models::UserName, response_models::UserPrivateInformation};
-use crate::api::security::authentication::ExtractUser;
-use crate::{unpack_result, unpack_result_option, AppState};
-use axum::extract::State;
+use crate::api::models::{
+    query_models::UserName,
+    response_models::{
+        AccountDeletion, BlockImportResult, FriendCount, FriendGraph, FriendGraphEdge, JoinRank,
+        PublicKeyInformation, RetentionStatus, SessionInformation, SessionList, UserAccountSummary,
+        UserPermissions, UserPrivateInformation, UserPublicInformation, UserRelationship,
+        UserSettingsInformation, UserSettingsSchema,
+    },
+};
+use crate::api::security::authentication::{ExtractUser, ExtractUserReadOnly};
+use crate::api::utils::fields::select_fields;
+use crate::api::utils::json_body::JsonBody;
+use crate::api::utils::limits::MAX_GRAPH_EXPORT_FAN_OUT;
+use crate::api::utils::time_operations::{nanos_to_date, timestamp_now_nanos};
+use crate::api::workers::inactive_sweeper::inactive_since_stamp;
+use crate::{unpack_result, unpack_result_bad_request, unpack_result_option, AppState};
+use axum::extract::{Path, State};
 use axum::response::Response;
-use axum::routing::{delete, patch, post};
+use axum::routing::{delete, patch, post, put};
 use axum::{extract::Query, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
 use axum_valid::Valid;
+use std::collections::{HashMap, HashSet};
 
 /// Retrieve own user information.
 // region: get_user
@@ -19,8 +42,10 @@ use axum_valid::Valid;
 #[utoipa::path(
     get,
     path = "/user",
+    params(FieldSelection),
     responses(
         (status = 200, description = "Personal private user information", body = UserPrivateInformation),
+        (status = 400, description = "Unknown fields requested"),
         (status = 401, description = "Invalid API Key"),
         (status = 500, description = "Server error"),
     ),
@@ -29,20 +54,242 @@ use axum_valid::Valid;
     ),
     tag = "User"
 )]
-async fn get_user(ExtractUser(user): ExtractUser) -> Json<UserPrivateInformation> {
-    Json(user.private_information())
+async fn get_user(ExtractUser(user): ExtractUser, fields: Query<FieldSelection>) -> Response {
+    let information = unpack_result_bad_request!(select_fields::<UserPrivateInformation>(
+        &user.private_information(),
+        &fields,
+        None
+    ));
+    Json(information).into_response()
 }
 // endregion: get_user
 
+/// Retrieve your account summary.
+// region: get_user_me
+/// This endpoint returns your private information, settings, permissions, friend and request counts and notification count at once.
+/// It's meant for clients to load their entire state with a single request.
+#[utoipa::path(
+    get,
+    path = "/user/me",
+    responses(
+        (status = 200, description = "Your account summary", body = UserAccountSummary),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_me(ExtractUser(user): ExtractUser, State(state): State<AppState>) -> Response {
+    let friends = unpack_result!(
+        user.friend_count(
+            &state.database.user_collection,
+            &state.database.friendship_collection
+        )
+        .await,
+        "An error occured while counting your friends"
+    );
+
+    let pending_sent = unpack_result!(
+        count_sent_friend_requests(&state.database.user_collection, &user.key).await,
+        "An error occured while counting your friend requests"
+    );
+
+    let notification_count = unpack_result!(
+        count_notifications_by_receiver_key(&state.database.notification_collection, &user.key)
+            .await,
+        "An error occured while counting your notifications"
+    );
+
+    Json(UserAccountSummary {
+        user: user.private_information(),
+        settings: user.settings_information(),
+        permissions: UserPermissions {
+            capabilities: user.permission_level.capabilities(),
+            permission_level: user.permission_level.clone(),
+        },
+        friend_count: FriendCount {
+            friends,
+            pending_received: user.friend_requests.len() as u64,
+            pending_sent,
+        },
+        notification_count,
+    })
+    .into_response()
+}
+// endregion: get_user_me
+
+/// Delete your account.
+// region: delete_user
+/// This endpoint schedules your account for deletion, it is hidden from other users right away.
+/// After the grace period your account, friendships, friend requests and notifications are permanently deleted.
+/// Until then you can restore your account using POST /user/undelete.
+#[utoipa::path(
+    delete,
+    path = "/user",
+    responses(
+        (status = 200, description = "Your account was scheduled for deletion", body = AccountDeletion),
+        (status = 403, description = "Your account is already scheduled for deletion"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn delete_user(
+    ExtractUser(mut user): ExtractUser,
+    State(state): State<AppState>,
+) -> Response {
+    let delete_after_stamp = match user.schedule_deletion(
+        timestamp_now_nanos(),
+        state.config.account_deletion_grace_nanos(),
+    ) {
+        Ok(stamp) => stamp,
+        Err(message) => return (StatusCode::FORBIDDEN, message).into_response(),
+    };
+
+    unpack_result!(
+        user.save(&state.database.user_collection).await,
+        "An error occured while saving the user"
+    );
+
+    Json(AccountDeletion {
+        deletion_date: nanos_to_date(delete_after_stamp, &user.timezone),
+    })
+    .into_response()
+}
+// endregion: delete_user
+
+/// Restore your account.
+// region: post_user_undelete
+/// This endpoint cancels the scheduled deletion of your account, as long as its grace period didn't end yet.
+#[utoipa::path(
+    post,
+    path = "/user/undelete",
+    responses(
+        (status = 200, description = "Your account was restored"),
+        (status = 400, description = "Your account is not scheduled for deletion or its grace period ended"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn post_user_undelete(
+    ExtractUser(mut user): ExtractUser,
+    State(state): State<AppState>,
+) -> Response {
+    if let Err(message) = user.restore(timestamp_now_nanos()) {
+        return Json((StatusCode::BAD_REQUEST, message)).into_response();
+    }
+
+    unpack_result!(
+        user.save(&state.database.user_collection).await,
+        "An error occured while saving the user"
+    );
+
+    Json((StatusCode::OK, "Your account was restored")).into_response()
+}
+// endregion: post_user_undelete
+
+/// Retrieve your data retention status.
+// region: get_user_retention
+/// This endpoint returns which automated data lifecycle actions affect your account and when they take place.
+/// It doesn't count as activity, so it can't clear the inactive flag of your account.
+#[utoipa::path(
+    get,
+    path = "/user/retention",
+    responses(
+        (status = 200, description = "Your data retention status", body = RetentionStatus),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_retention(
+    ExtractUserReadOnly(user): ExtractUserReadOnly,
+    State(state): State<AppState>,
+) -> Json<RetentionStatus> {
+    let inactive_date = (!state.config.inactive_sweep_dry_run).then(|| {
+        let stamp =
+            inactive_since_stamp(user.last_access_stamp, state.config.inactive_account_days);
+        nanos_to_date(stamp, &user.timezone)
+    });
+
+    Json(RetentionStatus {
+        inactive: user.inactive,
+        inactive_date,
+        deletion_date: user
+            .delete_after_stamp
+            .map(|stamp| nanos_to_date(stamp, &user.timezone)),
+    })
+}
+// endregion: get_user_retention
+
+/// Retrieve own permissions.
+// region: get_user_permission
+/// This endpoint returns your permission level and the capabilities it grants you.
+#[utoipa::path(
+    get,
+    path = "/user/permission",
+    responses(
+        (status = 200, description = "Your permission level and capabilities", body = UserPermissions),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_permission(ExtractUser(user): ExtractUser) -> Json<UserPermissions> {
+    Json(UserPermissions {
+        capabilities: user.permission_level.capabilities(),
+        permission_level: user.permission_level,
+    })
+}
+// endregion: get_user_permission
+
+/// Retrieve own feature flags.
+// region: get_user_flags
+/// This endpoint returns which experimental features are enabled for your account.
+#[utoipa::path(
+    get,
+    path = "/user/flags",
+    responses(
+        (status = 200, description = "Your feature flags", body = HashMap<String, bool>),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_flags(ExtractUser(user): ExtractUser) -> Json<HashMap<String, bool>> {
+    Json(user.resolved_feature_flags())
+}
+// endregion: get_user_flags
+
 /// Retrieve public user information.
 // region: get_user_search
 /// This endpoint returns the user information of the specified username, should they exist.
+/// If no user has this username, the user with a matching display name is returned instead, ignoring case and accents.
 #[utoipa::path(
     get,
     path = "/user/search",
-    params(UserName, IncludeUserProfile),
+    params(UserName, IncludeUserProfile, FieldSelection),
     responses(
         (status = 200, description = "Personal private user information", body = UserPrivateInformation),
+        (status = 400, description = "Unknown fields requested"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "User does not exist"),
         (status = 500, description = "Server error"),
@@ -57,16 +304,28 @@ async fn get_user_search(
     State(state): State<AppState>,
     name_query: Query<UserName>,
     profile_query: Query<IncludeUserProfile>,
+    fields: Query<FieldSelection>,
 ) -> Response {
-    let name_query = name_query.sanitize();
+    let sanitized_query = name_query.sanitize();
 
-    let target = unpack_result_option!(
-        find_user_by_name(&state.database.user_collection, &name_query.name).await,
-        StatusCode::NOT_FOUND,
-        "User not found",
+    let target = unpack_result!(
+        find_user_by_name(&state.database.user_collection, &sanitized_query.name).await,
         "An error occured while fetching user"
     );
 
+    let (target, found_by_display_name) = match target {
+        Some(target) => (target, false),
+        None => (
+            unpack_result_option!(
+                find_user_by_display_name(&state.database.user_collection, &name_query.name).await,
+                StatusCode::NOT_FOUND,
+                "User not found",
+                "An error occured while fetching user"
+            ),
+            true,
+        ),
+    };
+
     if target.block_list.contains_key(&user.key) || user.block_list.contains_key(&target.key) {
         return (StatusCode::NOT_FOUND, "User not found").into_response();
     }
@@ -74,7 +333,7 @@ async fn get_user_search(
     let is_friend = unpack_result!(
         are_friends(
             &state.database.friendship_collection,
-            vec![user.key, target.key.clone()],
+            vec![user.key.clone(), target.key.clone()],
         )
         .await,
         "An error occured while trying to fetch friendship"
@@ -84,15 +343,274 @@ async fn get_user_search(
         return (StatusCode::NOT_FOUND, "User not found").into_response();
     };
 
-    Json(target.public_information(
+    if found_by_display_name && target.settings.friends_only_discovery && !is_friend {
+        return (StatusCode::NOT_FOUND, "User not found").into_response();
+    };
+
+    let mut information = target.public_information(
         is_friend,
         profile_query.include_user_profile,
         &user.timezone,
-    ))
-    .into_response()
+        state.config.online_threshold_nanos(),
+    );
+
+    if !user.is_self(&target) && target.settings.show_mutual_friends.is_visible(is_friend) {
+        information.mutual_friends_count = Some(unpack_result!(
+            count_mutual_friends(
+                &state.database.friendship_collection,
+                &user.key,
+                &target.key
+            )
+            .await,
+            "An error occured while counting mutual friends"
+        ));
+    }
+
+    let information = unpack_result_bad_request!(select_fields::<UserPublicInformation>(
+        &information,
+        &fields,
+        None
+    ));
+    Json(information).into_response()
 }
 // endregion: get_user_search
 
+/// Retrieve a public key.
+// region: get_user_pubkey
+/// This endpoint returns the public key the specified user published, e.g. to encrypt messages for them.
+#[utoipa::path(
+    get,
+    path = "/user/pubkey",
+    params(UserName),
+    responses(
+        (status = 200, description = "The public key of the user", body = PublicKeyInformation),
+        (status = 400, description = "Username contains invalid characters"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "User not found or no public key published"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_pubkey(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserName>,
+) -> Response {
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
+
+    let target = unpack_result_option!(
+        find_user_by_name(&state.database.user_collection, &query.name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occured while fetching user"
+    );
+
+    if target.block_list.contains_key(&user.key) || user.block_list.contains_key(&target.key) {
+        return (StatusCode::NOT_FOUND, "User not found").into_response();
+    }
+
+    let Some(public_key) = target.public_key else {
+        return (StatusCode::NOT_FOUND, "User has not published a public key").into_response();
+    };
+
+    Json(PublicKeyInformation {
+        name: target.name,
+        public_key,
+    })
+    .into_response()
+}
+// endregion: get_user_pubkey
+
+/// Publish your public key.
+// region: put_user_pubkey
+/// This endpoint allows you to publish a public key other users can fetch, replacing your previous one.
+/// The key is stored as is and never used by the server.
+#[utoipa::path(
+    put,
+    path = "/user/pubkey",
+    request_body = PublicKeyUpload,
+    responses(
+        (status = 200, description = "Your published public key", body = PublicKeyInformation),
+        (status = 400, description = "Invalid public key"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn put_user_pubkey(
+    ExtractUser(mut user): ExtractUser,
+    State(state): State<AppState>,
+    Valid(JsonBody(body)): Valid<JsonBody<PublicKeyUpload>>,
+) -> Response {
+    user.public_key = Some(body.public_key.clone());
+    unpack_result!(
+        user.save(&state.database.user_collection).await,
+        "An error occured while saving your public key"
+    );
+
+    Json(PublicKeyInformation {
+        name: user.name,
+        public_key: body.public_key,
+    })
+    .into_response()
+}
+// endregion: put_user_pubkey
+
+/// Retrieve a join rank.
+// region: get_user_rank
+/// This endpoint returns at which position you or the specified user joined the network, e.g. rank 1234 for the 1234th member.
+/// The rank of other users is only available if you are allowed to see their join date.
+#[utoipa::path(
+    get,
+    path = "/user/rank",
+    params(OptionalUserName),
+    responses(
+        (status = 200, description = "The join rank of the user", body = JoinRank),
+        (status = 400, description = "Username contains invalid characters"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 403, description = "The join date of the user is private"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_rank(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<OptionalUserName>,
+) -> Response {
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
+
+    let target = match query.name {
+        None => user,
+        Some(name) => {
+            let target = unpack_result_option!(
+                find_user_by_name(&state.database.user_collection, &name).await,
+                StatusCode::NOT_FOUND,
+                "User not found",
+                "An error occured while fetching user"
+            );
+
+            if target.block_list.contains_key(&user.key)
+                || user.block_list.contains_key(&target.key)
+            {
+                return (StatusCode::NOT_FOUND, "User not found").into_response();
+            }
+
+            let is_friend = unpack_result!(
+                are_friends(
+                    &state.database.friendship_collection,
+                    vec![user.key.clone(), target.key.clone()],
+                )
+                .await,
+                "An error occured while trying to fetch friendship"
+            );
+
+            if !target.settings.show_join_date.is_visible(is_friend) {
+                return (
+                    StatusCode::FORBIDDEN,
+                    "The join date of the user is private",
+                )
+                    .into_response();
+            }
+            target
+        }
+    };
+
+    // Ranks only change when older accounts are deleted, so they are cached for a while
+    let rank = match state.join_rank_cache.get(&target.key) {
+        Some(rank) => rank,
+        None => {
+            let rank = unpack_result!(
+                join_rank(&state.database.user_collection, target.created_stamp).await,
+                "An error occured while computing the join rank"
+            );
+            state.join_rank_cache.insert(&target.key, rank);
+            rank
+        }
+    };
+
+    Json(JoinRank {
+        name: target.name,
+        rank,
+    })
+    .into_response()
+}
+// endregion: get_user_rank
+
+/// Retrieve your relationship with a user.
+// region: get_user_relationship
+/// This endpoint returns if you are friends with the specified user, if there are pending friend requests between you and if you blocked them.
+/// Users who blocked you are reported as not found.
+#[utoipa::path(
+    get,
+    path = "/user/relationship",
+    params(UserName),
+    responses(
+        (status = 200, description = "Your relationship with the user", body = UserRelationship),
+        (status = 400, description = "Username contains invalid characters"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_relationship(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<UserName>,
+) -> Response {
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
+
+    let target = unpack_result_option!(
+        find_user_by_name(&state.database.user_collection, &query.name).await,
+        StatusCode::NOT_FOUND,
+        "User not found",
+        "An error occured while fetching user"
+    );
+
+    // Users who can inspect other users see the block instead, everyone else can't tell it apart from a missing user
+    let can_inspect = user
+        .permission_level
+        .has_capability(Capability::InspectUsers);
+    if target.block_list.contains_key(&user.key) && !can_inspect {
+        return (StatusCode::NOT_FOUND, "User not found").into_response();
+    }
+
+    let is_friend = unpack_result!(
+        are_friends(
+            &state.database.friendship_collection,
+            vec![user.key.clone(), target.key.clone()],
+        )
+        .await,
+        "An error occured while trying to fetch friendship"
+    );
+
+    let is_blocked_by = can_inspect.then(|| target.block_list.contains_key(&user.key));
+
+    Json(UserRelationship {
+        is_friend,
+        incoming_request: user.friend_requests.contains_key(&target.key),
+        outgoing_request: target.friend_requests.contains_key(&user.key),
+        is_blocked: user.block_list.contains_key(&target.key),
+        is_blocked_by,
+    })
+    .into_response()
+}
+// endregion: get_user_relationship
+
 /// Retrieve own user settings.
 // region: get_user_settings
 /// This endpoint returns your user settings.
@@ -100,7 +618,7 @@ async fn get_user_search(
     get,
     path = "/user/settings",
     responses(
-        (status = 200, description = "Your user settings", body = UserSettings),
+        (status = 200, description = "Your user settings", body = UserSettingsInformation),
         (status = 401, description = "Invalid API Key"),
         (status = 500, description = "Server error"),
     ),
@@ -109,11 +627,34 @@ async fn get_user_search(
     ),
     tag = "User"
 )]
-async fn get_user_settings(ExtractUser(user): ExtractUser) -> Json<UserSettings> {
-    Json(user.settings)
+async fn get_user_settings(ExtractUser(user): ExtractUser) -> Json<UserSettingsInformation> {
+    Json(user.settings_information())
 }
 // endregion: get_user_search
 
+/// Retrieve own user settings with their accepted values.
+// region: get_user_settings_schema
+/// This endpoint returns your user settings named exactly like the parameters of PATCH /user/settings, together with all values each setting accepts.
+#[utoipa::path(
+    get,
+    path = "/user/settings/schema",
+    responses(
+        (status = 200, description = "Your user settings and their accepted values", body = UserSettingsSchema),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_settings_schema(ExtractUser(user): ExtractUser) -> Json<UserSettingsSchema> {
+    Json(UserSettingsSchema {
+        settings: user.settings.schema(),
+    })
+}
+// endregion: get_user_settings_schema
+
 /// Edit own user settings.
 // region: patch_user_settings
 /// This endpoint allows you to edit your own user settings.
@@ -122,8 +663,10 @@ async fn get_user_settings(ExtractUser(user): ExtractUser) -> Json<UserSettings>
     path = "/user/settings",
     params(UserSettingsEdit),
     responses(
-        (status = 200, description = "Your updated user settings", body = UserSettings),
+        (status = 200, description = "Your updated user settings", body = UserSettingsInformation),
+        (status = 400, description = "Invalid do not disturb times"),
         (status = 401, description = "Invalid API Key"),
+        (status = 429, description = "Settings were changed too recently"),
         (status = 500, description = "Server error"),
     ),
     security(
@@ -136,16 +679,91 @@ async fn patch_user_settings(
     State(state): State<AppState>,
     query: Query<UserSettingsEdit>,
 ) -> Response {
-    user.settings.update(query);
+    if let Err(response) = check_settings_cooldown(&state, &user).await {
+        return response;
+    }
+
+    if !query.has_valid_times() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Do not disturb times have to be in the format HH:MM",
+        )
+            .into_response();
+    }
+
+    user.update_settings(query);
 
     unpack_result!(
         user.save(&state.database.user_collection).await,
         "Failed to save user settings"
     );
-    Json(user.settings).into_response()
+    Json(user.settings_information()).into_response()
 }
 // endregion: patch_user_settings
 
+/// Reset own user settings.
+// region: post_user_settings_reset
+/// This endpoint restores the default value of all your settings.
+/// Your display name is kept unless you explicitly request to reset it too.
+#[utoipa::path(
+    post,
+    path = "/user/settings/reset",
+    params(SettingsReset),
+    responses(
+        (status = 200, description = "Your user settings after the reset", body = UserSettingsInformation),
+        (status = 401, description = "Invalid API Key"),
+        (status = 429, description = "Settings were changed too recently"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn post_user_settings_reset(
+    ExtractUser(mut user): ExtractUser,
+    State(state): State<AppState>,
+    query: Query<SettingsReset>,
+) -> Response {
+    if let Err(response) = check_settings_cooldown(&state, &user).await {
+        return response;
+    }
+
+    user.reset_settings(query.reset_display_name);
+
+    unpack_result!(
+        user.save(&state.database.user_collection).await,
+        "Failed to save user settings"
+    );
+    Json(user.settings_information()).into_response()
+}
+// endregion: post_user_settings_reset
+
+/// Rejects the change with a 429 if the settings were changed too recently, otherwise records it right away
+async fn check_settings_cooldown(state: &AppState, user: &User) -> Result<(), Response> {
+    let cooldown = state.config.settings_save_cooldown_nanos();
+    let now = timestamp_now_nanos();
+    let claimed = user.can_change_settings(cooldown, now)
+        && claim_settings_change(&state.database.user_collection, &user.key, cooldown, now)
+            .await
+            .map_err(|_| {
+                Json((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to save user settings",
+                ))
+                .into_response()
+            })?;
+
+    if !claimed {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Settings were changed too recently, try again in a few seconds",
+        )
+            .into_response());
+    }
+    Ok(())
+}
+
 /// Edit own user profile.
 // region: patch_user_profile
 /// This endpoint allows you to edit your own user profile.
@@ -201,9 +819,9 @@ async fn get_user_block(
     State(state): State<AppState>,
     query: Query<PaginationQuery>,
 ) -> Response {
-    let query = query.sanitize();
+    let query = query.sanitize(state.config);
     let page = query.page.unwrap_or(1);
-    let page_size = query.page_size.unwrap_or(10);
+    let page_size = query.page_size.unwrap_or(state.config.default_page_size);
 
     let block_list = unpack_result!(
         user.block_list_with_pagination(&state.database.user_collection, page, page_size)
@@ -224,7 +842,7 @@ async fn get_user_block(
     params(UserName),
     responses(
         (status = 200, description = "Successfully blocked user"),
-        (status = 400, description = "Unable to block user"),
+        (status = 400, description = "Unable to block user or username contains invalid characters"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Server error"),
@@ -239,7 +857,7 @@ async fn post_user_block(
     State(state): State<AppState>,
     query: Query<UserName>,
 ) -> Response {
-    let query = query.sanitize();
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
 
     let target = unpack_result_option!(
         find_user_by_name(&state.database.user_collection, &query.name).await,
@@ -248,7 +866,7 @@ async fn post_user_block(
         "An error occured while fetching user"
     );
 
-    if target.key == user.key {
+    if user.is_self(&target) {
         return (StatusCode::BAD_REQUEST, "You can't block yourself").into_response();
     };
 
@@ -283,6 +901,89 @@ async fn post_user_block(
 }
 // endregion: post_user_block
 
+/// Block multiple users at once.
+// region: post_user_block_import
+/// This endpoint allows you to block a list of users, e.g. when migrating from another platform.
+/// Pending friend requests from newly blocked users are removed.
+#[utoipa::path(
+    post,
+    path = "/user/block/import",
+    request_body = BlockImport,
+    responses(
+        (status = 200, description = "Which users were blocked, skipped, couldn't be found or have invalid usernames", body = BlockImportResult),
+        (status = 400, description = "Invalid list of usernames"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn post_user_block_import(
+    ExtractUser(mut user): ExtractUser,
+    State(state): State<AppState>,
+    Valid(JsonBody(body)): Valid<JsonBody<BlockImport>>,
+) -> Response {
+    let mut names: Vec<String> = Vec::new();
+    let mut invalid = Vec::new();
+    for name in body.names.iter() {
+        match (UserName { name: name.clone() }).try_sanitize(state.config) {
+            Ok(query) => {
+                let sanitized_name = query.name.to_lowercase();
+                if !sanitized_name.is_empty() && !names.contains(&sanitized_name) {
+                    names.push(sanitized_name);
+                }
+            }
+            Err(_) => invalid.push(name.clone()),
+        }
+    }
+
+    let targets = unpack_result!(
+        find_users_by_names(&state.database.user_collection, &names).await,
+        "An error occured while fetching users"
+    );
+
+    let friend_keys = unpack_result!(
+        find_friend_keys(&state.database.friendship_collection, &user.key).await,
+        "An error occured while fetching friendships"
+    );
+
+    let mut blocked = Vec::new();
+    let mut skipped = Vec::new();
+    for target in targets.iter() {
+        if user.is_self(target)
+            || friend_keys.contains(&target.key)
+            || user.block_user(&target.key).is_err()
+        {
+            skipped.push(target.name.clone());
+            continue;
+        }
+        blocked.push(target.name.clone());
+    }
+
+    unpack_result!(
+        user.save(&state.database.user_collection).await,
+        "An error occured while saving user"
+    );
+
+    let resolved: HashSet<&String> = targets.iter().map(|target| &target.name).collect();
+    let unresolved = names
+        .iter()
+        .filter(|name| !resolved.contains(name))
+        .cloned()
+        .collect();
+
+    Json(BlockImportResult {
+        blocked,
+        skipped,
+        unresolved,
+        invalid,
+    })
+    .into_response()
+}
+// endregion: post_user_block_import
+
 /// Unblock a user.
 // region: delete_user_block
 /// This endpoint allows you to unblock users.
@@ -292,7 +993,7 @@ async fn post_user_block(
     params(UserName),
     responses(
         (status = 200, description = "Successfully unblocked user"),
-        (status = 400, description = "Unable to unblock user"),
+        (status = 400, description = "Unable to unblock user or username contains invalid characters"),
         (status = 401, description = "Invalid API Key"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Server error"),
@@ -307,7 +1008,7 @@ async fn delete_user_block(
     State(state): State<AppState>,
     query: Query<UserName>,
 ) -> Response {
-    let query = query.sanitize();
+    let query = unpack_result_bad_request!(query.try_sanitize(state.config));
 
     let target = unpack_result_option!(
         find_user_by_name(&state.database.user_collection, &query.name).await,
@@ -330,14 +1031,204 @@ async fn delete_user_block(
 }
 // endregion: delete_user_block
 
+/// Export your friend graph.
+// region: get_user_graph_export
+/// This endpoint returns your friendships and the friendships of your friends as an edge list, e.g. for visualization tools.
+/// Users you couldn't discover otherwise are replaced by placeholder names, the amount of included friendships of your friends is limited.
+#[utoipa::path(
+    get,
+    path = "/user/graph/export",
+    responses(
+        (status = 200, description = "Your friend graph", body = FriendGraph),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_graph_export(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+) -> Response {
+    let own_friendships = unpack_result!(
+        find_friendships_by_key(&state.database.friendship_collection, &user.key).await,
+        "An error occured while fetching your friendships"
+    );
+    let friend_keys: HashSet<String> = own_friendships
+        .iter()
+        .flat_map(|friendship| friendship.keys.clone())
+        .filter(|key| *key != user.key)
+        .collect();
+
+    let mut second_hop = unpack_result!(
+        find_friendships_of_keys(
+            &state.database.friendship_collection,
+            &friend_keys.iter().cloned().collect::<Vec<_>>(),
+            MAX_GRAPH_EXPORT_FAN_OUT + 1
+        )
+        .await,
+        "An error occured while fetching friendships"
+    );
+    let truncated = second_hop.len() as i64 > MAX_GRAPH_EXPORT_FAN_OUT;
+    second_hop.truncate(MAX_GRAPH_EXPORT_FAN_OUT as usize);
+
+    let mut seen_pairs = HashSet::new();
+    let pairs: Vec<(String, String)> = own_friendships
+        .into_iter()
+        .chain(second_hop)
+        .filter_map(|friendship| {
+            let mut keys = friendship.keys;
+            keys.sort();
+            match keys.as_slice() {
+                [first, second] => Some((first.clone(), second.clone())),
+                _ => None,
+            }
+        })
+        .filter(|pair| seen_pairs.insert(pair.clone()))
+        .collect();
+
+    let other_keys: Vec<String> = pairs
+        .iter()
+        .flat_map(|(first, second)| [first, second])
+        .filter(|key| **key != user.key)
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let users = unpack_result!(
+        find_users_with_keys(&state.database.user_collection, &other_keys).await,
+        "An error occured while fetching users"
+    );
+
+    let mut names: HashMap<String, String> = users
+        .iter()
+        .filter(|other| {
+            friend_keys.contains(&other.key)
+                || (!other.settings.friends_only_discovery
+                    && other.settings.show_in_search.is_visible(false)
+                    && !other.block_list.contains_key(&user.key)
+                    && !user.block_list.contains_key(&other.key))
+        })
+        .map(|other| (other.key.clone(), other.name.clone()))
+        .collect();
+    names.insert(user.key.clone(), user.name.clone());
+
+    let mut hidden_count = 0;
+    let mut resolve_name = |key: &str| -> String {
+        if let Some(name) = names.get(key) {
+            return name.clone();
+        }
+        hidden_count += 1;
+        let placeholder = format!("hidden-{}", hidden_count);
+        names.insert(key.to_string(), placeholder.clone());
+        placeholder
+    };
+
+    let edges = pairs
+        .iter()
+        .map(|(first, second)| FriendGraphEdge {
+            source: resolve_name(first),
+            target: resolve_name(second),
+        })
+        .collect();
+
+    Json(FriendGraph { edges, truncated }).into_response()
+}
+// endregion: get_user_graph_export
+
+/// Retrieve your open connections.
+// region: get_user_sessions
+/// This endpoint returns your currently open event stream connections.
+#[utoipa::path(
+    get,
+    path = "/user/sessions",
+    responses(
+        (status = 200, description = "Your open connections", body = SessionList),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn get_user_sessions(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+) -> Json<SessionList> {
+    let sessions = state
+        .events
+        .connections(&user.key)
+        .into_iter()
+        .map(|(id, connected_stamp)| SessionInformation {
+            id,
+            connected_date: nanos_to_date(connected_stamp, &user.timezone),
+        })
+        .collect();
+
+    Json(SessionList { sessions })
+}
+// endregion: get_user_sessions
+
+/// Close one of your connections.
+// region: delete_user_session
+/// This endpoint allows you to force-close one of your open event stream connections, e.g. from a lost device.
+#[utoipa::path(
+    delete,
+    path = "/user/sessions/{id}",
+    params(
+        ("id" = u64, Path, description = "The id of the connection")
+    ),
+    responses(
+        (status = 200, description = "Connection closed"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 404, description = "Connection not found"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "User"
+)]
+async fn delete_user_session(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Response {
+    if !state.events.disconnect(&user.key, id) {
+        return (StatusCode::NOT_FOUND, "Connection not found").into_response();
+    }
+
+    (StatusCode::OK, "Connection closed").into_response()
+}
+// endregion: delete_user_session
+
 pub fn router() -> Router<AppState> {
     Router::<AppState>::new()
         .route("/user", get(get_user))
+        .route("/user", delete(delete_user))
+        .route("/user/undelete", post(post_user_undelete))
+        .route("/user/me", get(get_user_me))
+        .route("/user/flags", get(get_user_flags))
+        .route("/user/permission", get(get_user_permission))
+        .route("/user/pubkey", get(get_user_pubkey))
+        .route("/user/pubkey", put(put_user_pubkey))
+        .route("/user/rank", get(get_user_rank))
+        .route("/user/relationship", get(get_user_relationship))
+        .route("/user/retention", get(get_user_retention))
         .route("/user/search", get(get_user_search))
         .route("/user/settings", get(get_user_settings))
         .route("/user/settings", patch(patch_user_settings))
+        .route("/user/settings/reset", post(post_user_settings_reset))
+        .route("/user/settings/schema", get(get_user_settings_schema))
         .route("/user/profile", patch(patch_user_profile))
         .route("/user/block", get(get_user_block))
         .route("/user/block", post(post_user_block))
         .route("/user/block", delete(delete_user_block))
+        .route("/user/block/import", post(post_user_block_import))
+        .route("/user/graph/export", get(get_user_graph_export))
+        .route("/user/sessions", get(get_user_sessions))
+        .route("/user/sessions/:id", delete(delete_user_session))
 }
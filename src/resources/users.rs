@@ -1,25 +1,29 @@
-use crate::api::entities::friendship::are_friends;
-use crate::api::entities::user::get_public_users;
-use crate::api::models::query_models::IncludeUserProfile;
+use crate::api::entities::friendship::{are_friends, find_friend_keys};
+use crate::api::entities::user::{get_public_users, User};
+use crate::api::models::query_models::{FieldSelection, IncludeUserProfile};
 use crate::api::models::response_models::UserPublicInformation;
 use crate::api::models::{query_models::PaginationQuery, response_models::UserList};
 use crate::api::security::authentication::ExtractUser;
-use crate::{unpack_result, AppState};
+use crate::api::utils::fields::select_fields;
+use crate::{unpack_result, unpack_result_bad_request, AppState};
 use axum::extract::{Query, State};
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::{routing::get, Json, Router};
+use mongodb::bson::{doc, Document};
 
 /// Retrieve public users.
 // region: get_users
 /// This endpoint returns a list of users which are publicly visible.
 /// To be publicly visible, users have to set profile_public to true.
+/// Users with friends_only_discovery enabled are only listed if you are friends with them.
 #[utoipa::path(
     get,
     path = "/users",
-    params(PaginationQuery, IncludeUserProfile),
+    params(PaginationQuery, IncludeUserProfile, FieldSelection),
     responses(
         (status = 200, description = "Publicly visible users", body = UserList),
+        (status = 400, description = "Unknown fields requested"),
         (status = 401, description = "Invalid API Key"),
         (status = 500, description = "Server error"),
     ),
@@ -33,52 +37,161 @@ async fn get_users(
     State(state): State<AppState>,
     pagination: Query<PaginationQuery>,
     profile_query: Query<IncludeUserProfile>,
+    fields: Query<FieldSelection>,
 ) -> Response {
-    let pagination = pagination.sanitize();
+    let pagination = pagination.sanitize(state.config);
 
     let page = pagination.page.unwrap_or(1);
-    let page_size = pagination.page_size.unwrap_or(10);
+    let page_size = pagination
+        .page_size
+        .unwrap_or(state.config.default_page_size);
 
-    let mut excluded_keys: Vec<String> = user.block_list.keys().cloned().collect();
-    excluded_keys.push(user.key.clone());
-
-    let (users, pagination) = unpack_result!(
-        get_public_users(
-            &state.database.user_collection,
-            excluded_keys,
-            &user.key,
+    let user_list = unpack_result!(
+        public_user_list(
+            &user,
+            &state,
             page,
-            page_size
+            page_size,
+            profile_query.include_user_profile,
+            None
         )
         .await,
         "An error occured while fetching users"
     );
 
+    let user_list = unpack_result_bad_request!(select_fields::<UserPublicInformation>(
+        &user_list,
+        &fields,
+        Some("users")
+    ));
+    Json(user_list).into_response()
+}
+// endregion: get_users
+
+/// Retrieve recently joined public users.
+// region: get_users_recent
+/// This endpoint returns the publicly visible users sorted by when they joined, newest first.
+/// The same visibility rules as for GET /users apply.
+#[utoipa::path(
+    get,
+    path = "/users/recent",
+    params(PaginationQuery, IncludeUserProfile, FieldSelection),
+    responses(
+        (status = 200, description = "Recently joined publicly visible users", body = UserList),
+        (status = 400, description = "Unknown fields requested"),
+        (status = 401, description = "Invalid API Key"),
+        (status = 500, description = "Server error"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "Users"
+)]
+async fn get_users_recent(
+    ExtractUser(user): ExtractUser,
+    State(state): State<AppState>,
+    pagination: Query<PaginationQuery>,
+    profile_query: Query<IncludeUserProfile>,
+    fields: Query<FieldSelection>,
+) -> Response {
+    let pagination = pagination.sanitize(state.config);
+
+    let page = pagination.page.unwrap_or(1);
+    let page_size = pagination
+        .page_size
+        .unwrap_or(state.config.default_page_size);
+
+    // The first page changes slowly but is requested the most, so it is cached briefly
+    let cache_key = format!(
+        "{}:{}:{}",
+        user.key, page_size, profile_query.include_user_profile
+    );
+    let cached_user_list = if page == 1 {
+        state.recent_users_cache.get(&cache_key)
+    } else {
+        None
+    };
+
+    let user_list = match cached_user_list {
+        Some(user_list) => user_list,
+        None => {
+            let user_list = unpack_result!(
+                public_user_list(
+                    &user,
+                    &state,
+                    page,
+                    page_size,
+                    profile_query.include_user_profile,
+                    Some(doc! { "created_stamp": -1 })
+                )
+                .await,
+                "An error occured while fetching users"
+            );
+            if page == 1 {
+                state
+                    .recent_users_cache
+                    .insert(&cache_key, user_list.clone());
+            }
+            user_list
+        }
+    };
+
+    let user_list = unpack_result_bad_request!(select_fields::<UserPublicInformation>(
+        &user_list,
+        &fields,
+        Some("users")
+    ));
+    Json(user_list).into_response()
+}
+// endregion: get_users_recent
+
+async fn public_user_list(
+    user: &User,
+    state: &AppState,
+    page: u32,
+    page_size: u32,
+    include_profile: bool,
+    sort: Option<Document>,
+) -> mongodb::error::Result<UserList> {
+    let mut excluded_keys: Vec<String> = user.block_list.keys().cloned().collect();
+    excluded_keys.push(user.key.clone());
+
+    let friend_keys = find_friend_keys(&state.database.friendship_collection, &user.key).await?;
+
+    let (users, pagination) = get_public_users(
+        &state.database.user_collection,
+        excluded_keys,
+        friend_keys.into_iter().collect(),
+        &user.key,
+        page,
+        page_size,
+        sort,
+    )
+    .await?;
+
     let mut public_information: Vec<UserPublicInformation> = Vec::new();
     for target in users.iter() {
-        let is_friend = unpack_result!(
-            are_friends(
-                &state.database.friendship_collection,
-                vec![user.key.clone(), target.key.clone()]
-            )
-            .await,
-            "An error occured while fetching friendship"
-        );
+        let is_friend = are_friends(
+            &state.database.friendship_collection,
+            vec![user.key.clone(), target.key.clone()],
+        )
+        .await?;
         public_information.push(target.public_information(
             is_friend,
-            profile_query.include_user_profile,
+            include_profile,
             &user.timezone,
+            state.config.online_threshold_nanos(),
         ));
     }
 
-    let user_list = UserList {
+    Ok(UserList {
         users: public_information,
         pagination,
-    };
-    Json(user_list).into_response()
+    })
 }
-// endregion: get_users
 
 pub fn router() -> Router<AppState> {
-    Router::<AppState>::new().route("/users", get(get_users))
+    Router::<AppState>::new()
+        .route("/users", get(get_users))
+        .route("/users/recent", get(get_users_recent))
 }
@@ -0,0 +1,189 @@
+use crate::api::entities::user::User;
+use crate::api::models::enums::PermissionLevel;
+use crate::api::models::response_models::EndpointInformation;
+use crate::docs::ApiDoc;
+use lazy_static::lazy_static;
+use utoipa::{openapi::PathItemType, OpenApi};
+
+/// The documentation tag of the endpoints which require administrator permissions, a test checks it against the `RequireAdmin` extractor
+const ADMIN_TAG: &str = "Admin";
+
+lazy_static! {
+    /// Every documented endpoint together with who is allowed to use it
+    static ref ENDPOINTS: Vec<(EndpointInformation, AccessLevel)> = documented_endpoints();
+}
+
+/// Who is allowed to use an endpoint
+#[derive(Clone, Copy)]
+pub enum AccessLevel {
+    /// No API key is required
+    Public,
+    /// Any valid API key
+    User,
+    Administrator,
+}
+
+impl AccessLevel {
+    /// Checks if the user may use endpoints of this level, this is what the authentication extractors enforce
+    pub fn allows(&self, user: &User) -> bool {
+        match self {
+            AccessLevel::Public | AccessLevel::User => true,
+            AccessLevel::Administrator => user.permission_level >= PermissionLevel::Administrator,
+        }
+    }
+}
+
+/// The documented endpoints the user is currently allowed to use
+pub fn allowed_endpoints(user: &User) -> Vec<EndpointInformation> {
+    ENDPOINTS
+        .iter()
+        .filter(|(_, access)| access.allows(user))
+        .map(|(endpoint, _)| endpoint.clone())
+        .collect()
+}
+
+fn documented_endpoints() -> Vec<(EndpointInformation, AccessLevel)> {
+    let mut endpoints = Vec::new();
+    for (path, item) in ApiDoc::openapi().paths.paths {
+        for (method, operation) in item.operations {
+            let is_admin = operation
+                .tags
+                .is_some_and(|tags| tags.iter().any(|tag| tag == ADMIN_TAG));
+            let access = if is_admin {
+                AccessLevel::Administrator
+            } else if operation.security.is_some() {
+                AccessLevel::User
+            } else {
+                AccessLevel::Public
+            };
+
+            let endpoint = EndpointInformation {
+                method: method_name(&method).to_string(),
+                path: path.clone(),
+            };
+            endpoints.push((endpoint, access));
+        }
+    }
+    endpoints
+}
+
+pub fn method_name(method: &PathItemType) -> &'static str {
+    match method {
+        PathItemType::Get => "GET",
+        PathItemType::Post => "POST",
+        PathItemType::Put => "PUT",
+        PathItemType::Delete => "DELETE",
+        PathItemType::Options => "OPTIONS",
+        PathItemType::Head => "HEAD",
+        PathItemType::Patch => "PATCH",
+        PathItemType::Trace => "TRACE",
+        PathItemType::Connect => "CONNECT",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docs::RESOURCE_SOURCES;
+    use mongodb::bson::{self, doc};
+
+    fn user_with_level(level: &str) -> User {
+        bson::from_document(doc! {
+            "key": "key",
+            "name": "name",
+            "display_name": "name",
+            "created_stamp": 0_i64,
+            "permission_level": level,
+        })
+        .unwrap()
+    }
+
+    /// The method, path and access level the extractor of every documented handler enforces
+    fn enforced_endpoints() -> Vec<(String, String, &'static str)> {
+        let mut endpoints = Vec::new();
+        // The access level of an endpoint is decided by the extractor its handler takes
+        for (_, source) in RESOURCE_SOURCES {
+            for handler in source.split("#[utoipa::path(").skip(1) {
+                let method = handler.split_whitespace().next().unwrap();
+                let path = handler
+                    .split("path = \"")
+                    .nth(1)
+                    .and_then(|rest| rest.split('"').next())
+                    .unwrap();
+                let signature = handler
+                    .split("async fn ")
+                    .nth(1)
+                    .and_then(|rest| rest.split(") ->").next())
+                    .unwrap();
+                let access = if signature.contains("RequireAdmin") {
+                    "Administrator"
+                } else if signature.contains("ExtractUser") {
+                    "User"
+                } else {
+                    "Public"
+                };
+                endpoints.push((
+                    method.trim_end_matches(',').to_uppercase(),
+                    path.to_string(),
+                    access,
+                ));
+            }
+        }
+        endpoints
+    }
+
+    fn access_name(access: &AccessLevel) -> &'static str {
+        match access {
+            AccessLevel::Public => "Public",
+            AccessLevel::User => "User",
+            AccessLevel::Administrator => "Administrator",
+        }
+    }
+
+    #[test]
+    fn documented_access_matches_extractors() {
+        let enforced = enforced_endpoints();
+        assert_eq!(enforced.len(), ENDPOINTS.len());
+
+        for (method, path, access) in enforced {
+            let (_, documented) = ENDPOINTS
+                .iter()
+                .find(|(endpoint, _)| endpoint.method == method && endpoint.path == path)
+                .unwrap_or_else(|| panic!("{method} {path} is not documented"));
+            assert_eq!(access_name(documented), access, "{method} {path}");
+        }
+    }
+
+    #[test]
+    fn administrator_access_requires_administrator_level() {
+        for (level, allowed) in [
+            ("User", false),
+            ("Moderator", false),
+            ("Administrator", true),
+            ("Owner", true),
+        ] {
+            let user = user_with_level(level);
+            assert!(AccessLevel::Public.allows(&user));
+            assert!(AccessLevel::User.allows(&user));
+            assert_eq!(AccessLevel::Administrator.allows(&user), allowed, "{level}");
+        }
+    }
+
+    #[test]
+    fn allowed_endpoints_per_permission_level() {
+        let is_admin_endpoint =
+            |endpoint: &EndpointInformation| endpoint.path.starts_with("/admin");
+
+        for level in ["User", "Moderator"] {
+            let endpoints = allowed_endpoints(&user_with_level(level));
+            assert!(!endpoints.is_empty());
+            assert!(!endpoints.iter().any(is_admin_endpoint), "{level}");
+        }
+
+        for level in ["Administrator", "Owner"] {
+            let endpoints = allowed_endpoints(&user_with_level(level));
+            assert_eq!(endpoints.len(), ENDPOINTS.len(), "{level}");
+            assert!(endpoints.iter().any(is_admin_endpoint), "{level}");
+        }
+    }
+}
@@ -1,40 +1,35 @@
 use crate::{
-    api::entities::user::{find_user_by_key, User},
+    api::{
+        entities::user::{find_user_by_key, User},
+        security::access::AccessLevel,
+        utils::time_operations::timestamp_now_nanos,
+    },
     AppState,
 };
 use axum::{
     async_trait,
     extract::FromRequestParts,
-    http::{request::Parts, HeaderName, StatusCode},
+    http::{request::Parts, HeaderMap, HeaderName, Method, StatusCode},
 };
 
+/// The only change an account which is pending deletion can make
+const RESTORE_PATH: &str = "/user/undelete";
+
 pub struct ExtractUser(pub User);
 
+/// Extracts the requesting user without recording the request, so checking a key causes no database writes
+pub struct ExtractUserReadOnly(pub User);
+
 #[async_trait]
-impl FromRequestParts<AppState> for ExtractUser {
+impl FromRequestParts<AppState> for ExtractUserReadOnly {
     type Rejection = (StatusCode, &'static str);
 
     async fn from_request_parts(
         parts: &mut Parts,
         state: &AppState,
     ) -> Result<Self, Self::Rejection> {
-        let api_key_header = HeaderName::from_static("x-api-key");
-        let api_key = parts
-            .headers
-            .get(&api_key_header)
-            .ok_or((
-                StatusCode::UNAUTHORIZED,
-                "API key header is missing, check /docs for more information",
-            ))?
-            .to_str()
-            .map_err(|_| {
-                (
-                    StatusCode::UNAUTHORIZED,
-                    "Invalid API key format, check /docs for more information",
-                )
-            })?;
-
-        let mut user = find_user_by_key(&state.database.user_collection, api_key)
+        let api_key = api_key(&parts.headers)?;
+        let user = find_user_by_key(&state.database.user_collection, api_key)
             .await
             .map_err(|_| {
                 (
@@ -47,19 +42,178 @@ impl FromRequestParts<AppState> for ExtractUser {
                 "Invalid API key, check /docs for more information",
             ))?;
 
-        let method = parts.method.as_str();
-        let path = parts.uri.path();
+        Ok(ExtractUserReadOnly(user))
+    }
+}
 
-        user.use_endpoint(method, path);
-        user.save(&state.database.user_collection)
-            .await
-            .map_err(|_| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "An error occured while trying to save user",
-                )
-            })?;
+/// Reads the API key from the request headers
+fn api_key(headers: &HeaderMap) -> Result<&str, (StatusCode, &'static str)> {
+    headers
+        .get(HeaderName::from_static("x-api-key"))
+        .ok_or((
+            StatusCode::UNAUTHORIZED,
+            "API key header is missing, check /docs for more information",
+        ))?
+        .to_str()
+        .map_err(|_| {
+            (
+                StatusCode::UNAUTHORIZED,
+                "Invalid API key format, check /docs for more information",
+            )
+        })
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ExtractUser {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ExtractUserReadOnly(mut user) =
+            ExtractUserReadOnly::from_request_parts(parts, state).await?;
+
+        if user.delete_after_stamp.is_some() && !allowed_while_pending_deletion(parts) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Your account is scheduled for deletion, restore it using POST /user/undelete",
+            ));
+        }
+
+        let stamp = timestamp_now_nanos();
+        user.mark_accessed(stamp);
+        state
+            .access_buffer
+            .record(&user.key, stamp, parts.method.as_str(), parts.uri.path());
 
         Ok(ExtractUser(user))
     }
 }
+
+/// Accounts which are pending deletion can still read, but not change anything except restoring the account
+fn allowed_while_pending_deletion(parts: &Parts) -> bool {
+    parts.method == Method::GET
+        || parts.method == Method::HEAD
+        || (parts.method == Method::POST && parts.uri.path() == RESTORE_PATH)
+}
+
+/// Extracts the requesting user like `ExtractUser`, rejecting with 403 if they are not at least an administrator
+pub struct RequireAdmin(pub User);
+
+#[async_trait]
+impl FromRequestParts<AppState> for RequireAdmin {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ExtractUser(user) = ExtractUser::from_request_parts(parts, state).await?;
+        require_admin(user).map(RequireAdmin)
+    }
+}
+
+fn require_admin(user: User) -> Result<User, (StatusCode, &'static str)> {
+    if !AccessLevel::Administrator.allows(&user) {
+        return Err((StatusCode::FORBIDDEN, "Insufficient permissions"));
+    }
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderValue, Request};
+    use mongodb::bson::{self, doc};
+
+    fn parts(method: Method, path: &str) -> Parts {
+        let (parts, _) = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(())
+            .unwrap()
+            .into_parts();
+        parts
+    }
+
+    fn user_with_level(level: &str) -> User {
+        bson::from_document(doc! {
+            "key": "key",
+            "name": "name",
+            "display_name": "name",
+            "created_stamp": 0_i64,
+            "permission_level": level,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn pending_deletion_allows_reading_and_restoring() {
+        assert!(allowed_while_pending_deletion(&parts(Method::GET, "/user")));
+        assert!(allowed_while_pending_deletion(&parts(
+            Method::POST,
+            RESTORE_PATH
+        )));
+    }
+
+    #[test]
+    fn pending_deletion_rejects_changes() {
+        assert!(!allowed_while_pending_deletion(&parts(
+            Method::DELETE,
+            "/user"
+        )));
+        assert!(!allowed_while_pending_deletion(&parts(
+            Method::POST,
+            "/friend/request"
+        )));
+        assert!(!allowed_while_pending_deletion(&parts(
+            Method::PATCH,
+            "/user/settings"
+        )));
+    }
+
+    #[test]
+    fn require_admin_allows_administrators_and_owners() {
+        assert!(require_admin(user_with_level("Administrator")).is_ok());
+        assert!(require_admin(user_with_level("Owner")).is_ok());
+    }
+
+    #[test]
+    fn require_admin_rejects_lower_levels() {
+        for level in ["User", "Moderator"] {
+            assert_eq!(
+                require_admin(user_with_level(level)).err(),
+                Some((StatusCode::FORBIDDEN, "Insufficient permissions"))
+            );
+        }
+    }
+
+    #[test]
+    fn api_key_is_read_from_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("key"));
+        assert_eq!(api_key(&headers), Ok("key"));
+    }
+
+    #[test]
+    fn api_key_rejects_missing_or_invalid_header() {
+        assert_eq!(
+            api_key(&HeaderMap::new()),
+            Err((
+                StatusCode::UNAUTHORIZED,
+                "API key header is missing, check /docs for more information"
+            ))
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_bytes(b"k\xffey").unwrap());
+        assert_eq!(
+            api_key(&headers),
+            Err((
+                StatusCode::UNAUTHORIZED,
+                "Invalid API key format, check /docs for more information"
+            ))
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Mutex},
+};
+
+/// The accesses of a single user which haven't been written to the database yet
+#[derive(Default)]
+pub struct PendingAccess {
+    pub last_access_stamp: u64,
+    pub endpoint_usage: HashMap<String, u64>,
+}
+
+/// Collects user accesses in memory so they can be written to the database in bulk
+#[derive(Clone, Default)]
+pub struct AccessBuffer {
+    pending: Arc<Mutex<HashMap<String, PendingAccess>>>,
+}
+
+impl AccessBuffer {
+    pub fn record(&self, key: &str, stamp: u64, method: &str, path: &str) {
+        let mut pending = self.pending.lock().expect("Access buffer lock poisoned");
+        let access = pending.entry(key.to_string()).or_default();
+        access.last_access_stamp = access.last_access_stamp.max(stamp);
+        *access
+            .endpoint_usage
+            .entry(format!("{method} {path}"))
+            .or_insert(0) += 1;
+    }
+
+    /// Removes and returns everything recorded since the last call
+    pub fn take(&self) -> HashMap<String, PendingAccess> {
+        let mut pending = self.pending.lock().expect("Access buffer lock poisoned");
+        mem::take(&mut *pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_collects_accesses_per_user() {
+        let buffer = AccessBuffer::default();
+        buffer.record("key", 200, "GET", "/user");
+        buffer.record("key", 100, "GET", "/user");
+        buffer.record("key", 150, "POST", "/friend/request");
+        buffer.record("other", 50, "GET", "/user");
+
+        let pending = buffer.take();
+        let access = &pending["key"];
+        assert_eq!(access.last_access_stamp, 200);
+        assert_eq!(access.endpoint_usage["GET /user"], 2);
+        assert_eq!(access.endpoint_usage["POST /friend/request"], 1);
+        assert_eq!(pending["other"].last_access_stamp, 50);
+    }
+
+    #[test]
+    fn take_empties_buffer() {
+        let buffer = AccessBuffer::default();
+        buffer.record("key", 100, "GET", "/user");
+
+        assert_eq!(buffer.take().len(), 1);
+        assert!(buffer.take().is_empty());
+    }
+}
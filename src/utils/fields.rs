@@ -0,0 +1,56 @@
+use crate::api::models::query_models::FieldSelection;
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::{
+    openapi::{RefOr, Schema},
+    ToSchema,
+};
+
+/// Serializes a response and removes every field which wasn't requested, either from the response itself
+/// or from every item of the list with the given key. Unknown field names are rejected with a message.
+pub fn select_fields<'s, T: ToSchema<'s>>(
+    response: &impl Serialize,
+    selection: &FieldSelection,
+    list_key: Option<&str>,
+) -> Result<Value, String> {
+    let mut value = serde_json::to_value(response).map_err(|e| e.to_string())?;
+    let Some(fields) = selection.fields() else {
+        return Ok(value);
+    };
+
+    let known_fields = schema_fields::<T>();
+    let unknown_fields: Vec<&str> = fields
+        .iter()
+        .filter(|field| !known_fields.iter().any(|known| known == *field))
+        .copied()
+        .collect();
+    if !unknown_fields.is_empty() {
+        return Err(format!("Unknown fields: {}", unknown_fields.join(", ")));
+    }
+
+    match list_key {
+        Some(key) => {
+            if let Some(Value::Array(items)) = value.get_mut(key) {
+                for item in items {
+                    retain_fields(item, &fields);
+                }
+            }
+        }
+        None => retain_fields(&mut value, &fields),
+    }
+    Ok(value)
+}
+
+fn retain_fields(value: &mut Value, fields: &[&str]) {
+    if let Value::Object(object) = value {
+        object.retain(|key, _| fields.contains(&key.as_str()));
+    }
+}
+
+/// The field names of a response model, taken from its OpenAPI schema so they never go out of sync
+fn schema_fields<'s, T: ToSchema<'s>>() -> Vec<String> {
+    match T::schema().1 {
+        RefOr::T(Schema::Object(object)) => object.properties.into_keys().collect(),
+        _ => vec![],
+    }
+}
@@ -0,0 +1,104 @@
+use crate::api::models::response_models::MessageResponse;
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_valid::HasValidate;
+use serde::de::DeserializeOwned;
+
+/// Like axum's `Json` extractor, but responds with a 400 and a JSON message naming the offending field if the body can't be deserialized
+pub struct JsonBody<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(request, state).await {
+            Ok(Json(value)) => Ok(JsonBody(value)),
+            Err(rejection) => {
+                let status = match rejection {
+                    JsonRejection::JsonDataError(_) | JsonRejection::JsonSyntaxError(_) => {
+                        StatusCode::BAD_REQUEST
+                    }
+                    _ => rejection.status(),
+                };
+                let response = MessageResponse {
+                    message: rejection.body_text(),
+                };
+                Err((status, Json(response)).into_response())
+            }
+        }
+    }
+}
+
+impl<T> HasValidate for JsonBody<T> {
+    type Validate = T;
+    fn get_validate(&self) -> &T {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{to_bytes, Body},
+        http::header::CONTENT_TYPE,
+    };
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Counter {
+        count: u32,
+    }
+
+    fn request(body: &'static str) -> Request {
+        Request::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn rejection_message(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let response: MessageResponse = serde_json::from_slice(&body).unwrap();
+        response.message
+    }
+
+    #[tokio::test]
+    async fn extracts_valid_body() {
+        let JsonBody(counter) = JsonBody::<Counter>::from_request(request(r#"{"count": 3}"#), &())
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(counter.count, 3);
+    }
+
+    #[tokio::test]
+    async fn invalid_field_is_a_json_bad_request() {
+        let response = JsonBody::<Counter>::from_request(request(r#"{"count": "three"}"#), &())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(rejection_message(response).await.contains("count"));
+    }
+
+    #[tokio::test]
+    async fn malformed_json_is_a_json_bad_request() {
+        let response = JsonBody::<Counter>::from_request(request("{"), &())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(!rejection_message(response).await.is_empty());
+    }
+}
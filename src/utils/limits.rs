@@ -0,0 +1,20 @@
+pub const MAX_PRONOUNS_LENGTH: u64 = 64;
+pub const MAX_BIO_LENGTH: u64 = 4096;
+pub const MAX_STATUS_LENGTH: u64 = 128;
+pub const MAX_MOOD_LENGTH: u64 = 32;
+pub const MAX_INTERESTS: u64 = 20;
+pub const MAX_INTEREST_LENGTH: u64 = 128;
+/// The maximum amount of friendships of your friends considered when suggesting new friends
+pub const MAX_SUGGESTION_FAN_OUT: i64 = 1000;
+/// The maximum amount of friendships of your friends included in a friend graph export
+pub const MAX_GRAPH_EXPORT_FAN_OUT: i64 = 5000;
+/// The maximum amount of friend requests which can be answered at once
+pub const MAX_FRIEND_REQUEST_BATCH_SIZE: u64 = 50;
+/// The maximum length of a published public key
+pub const MAX_PUBLIC_KEY_LENGTH: u64 = 8192;
+/// The minimum amount of seconds between two presence pings of the same user
+pub const MIN_PRESENCE_PING_INTERVAL_SECONDS: u64 = 5;
+/// The maximum amount of usernames which can be blocked at once
+pub const MAX_BLOCK_IMPORT_SIZE: u64 = 100;
+/// The amount of users whose friend requests are checked together during the orphaned request cleanup
+pub const ORPHAN_CLEANUP_BATCH_SIZE: usize = 500;
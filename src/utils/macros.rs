@@ -50,3 +50,38 @@ macro_rules! unpack_result_option {
         }
     };
 }
+
+/// Unpacks a `Result` value, returning the contained value if `Ok` or terminating the function and
+/// responding with a bad request containing the error description if `Err`.
+/// # Parameters
+/// - `$expr`: The `Result` expression to unpack, its error has to implement `Display`.
+#[macro_export]
+macro_rules! unpack_result_bad_request {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        }
+    };
+}
+
+/// Unpacks a `Result` value like `unpack_result!`, but responds with a conflict instead of an
+/// internal server error if the error was caused by a duplicate key.
+/// # Parameters
+/// - `$expr`: The `Result` expression to unpack.
+/// - `$conflict_message`: The message to return in the JSON response if a duplicate key occurs.
+/// - `$error_message`: The message to return in the JSON response if any other error occurs.
+#[macro_export]
+macro_rules! unpack_result_conflict {
+    ($expr:expr, $conflict_message:expr, $error_message:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(e) if $crate::api::database::db::is_duplicate_key_error(&e) => {
+                return Json((StatusCode::CONFLICT, $conflict_message)).into_response()
+            }
+            Err(_) => {
+                return Json((StatusCode::INTERNAL_SERVER_ERROR, $error_message)).into_response()
+            }
+        }
+    };
+}
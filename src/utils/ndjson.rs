@@ -0,0 +1,95 @@
+use axum::{
+    body::Body,
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        HeaderMap, HeaderName, HeaderValue,
+    },
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+/// Carries the total amount of results of a streamed listing, since there is no pagination object
+pub const TOTAL_COUNT_HEADER: HeaderName = HeaderName::from_static("x-total-count");
+
+/// If the client asked for newline delimited JSON instead of a regular JSON document
+pub fn accepts_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_type| {
+            let name = media_type.split(';').next().unwrap_or_default().trim();
+            name.eq_ignore_ascii_case(NDJSON_CONTENT_TYPE)
+        })
+}
+
+/// Streams every item as its own line of JSON while it comes in, instead of buffering the whole response
+pub fn ndjson_response<S, T, E>(items: S, total: u64) -> Response
+where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Serialize,
+    E: Into<BoxError>,
+{
+    let lines = items.map(|item| -> Result<Vec<u8>, BoxError> {
+        let mut line = serde_json::to_vec(&item.map_err(Into::into)?)?;
+        line.push(b'\n');
+        Ok(line)
+    });
+
+    (
+        [
+            (CONTENT_TYPE, HeaderValue::from_static(NDJSON_CONTENT_TYPE)),
+            (TOTAL_COUNT_HEADER, HeaderValue::from(total)),
+        ],
+        Body::from_stream(lines),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use std::convert::Infallible;
+
+    fn accept(values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(ACCEPT, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn accepts_ndjson_among_other_media_types() {
+        assert!(accepts_ndjson(&accept(&["application/x-ndjson"])));
+        assert!(accepts_ndjson(&accept(&[
+            "application/json;q=0.9, Application/X-NDJSON; q=1"
+        ])));
+        assert!(accepts_ndjson(&accept(&[
+            "application/json",
+            "application/x-ndjson"
+        ])));
+    }
+
+    #[test]
+    fn defaults_to_regular_json() {
+        assert!(!accepts_ndjson(&HeaderMap::new()));
+        assert!(!accepts_ndjson(&accept(&["application/json, */*"])));
+    }
+
+    #[tokio::test]
+    async fn ndjson_response_writes_one_line_per_item() {
+        let items = futures::stream::iter([Ok::<_, Infallible>(1), Ok(2), Ok(3)]);
+        let response = ndjson_response(items, 3);
+
+        assert_eq!(response.headers()[CONTENT_TYPE], NDJSON_CONTENT_TYPE);
+        assert_eq!(response.headers()[TOTAL_COUNT_HEADER], "3");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"1\n2\n3\n");
+    }
+}
@@ -1,4 +1,6 @@
 use rustrict::CensorStr;
+use std::{collections::HashSet, fmt};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 
 pub fn alphanumeric(input: &str) -> String {
     input
@@ -7,6 +9,41 @@ pub fn alphanumeric(input: &str) -> String {
         .collect()
 }
 
+/// The characters which were rejected by a strict sanitization
+#[derive(Debug)]
+pub struct InvalidChars(pub Vec<char>);
+
+impl fmt::Display for InvalidChars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chars: Vec<String> = self.0.iter().map(|c| format!("'{c}'")).collect();
+        write!(f, "Invalid characters: {}", chars.join(", "))
+    }
+}
+
+/// Like `alphanumeric`, but rejects the input instead of stripping the characters which aren't allowed
+pub fn try_alphanumeric(input: &str) -> Result<String, InvalidChars> {
+    let mut invalid: Vec<char> = input
+        .chars()
+        .filter(|c| !c.is_ascii_alphanumeric())
+        .collect();
+    if invalid.is_empty() {
+        return Ok(input.to_string());
+    }
+    let mut seen = HashSet::new();
+    invalid.retain(|c| seen.insert(*c));
+    Err(InvalidChars(invalid))
+}
+
+/// Normalizes a search term so that case, accents and full-width characters don't matter,
+/// e.g. "José" and "ＪＯＳＥ" both become "jose".
+pub fn normalize_search_term(input: &str) -> String {
+    input
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
 pub fn profanity(input: &str) -> String {
     input.censor()
 }
@@ -30,3 +67,45 @@ pub fn limit_strings(input: &Vec<String>, size: usize, filter_profanity: bool) -
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_alphanumeric_accepts_valid_names() {
+        assert_eq!(try_alphanumeric("Lem42").unwrap(), "Lem42");
+    }
+
+    #[test]
+    fn try_alphanumeric_reports_each_invalid_character_once() {
+        let InvalidChars(invalid) = try_alphanumeric("a-b_c-d_e!").unwrap_err();
+        assert_eq!(invalid, vec!['-', '_', '!']);
+    }
+
+    #[test]
+    fn normalize_search_term_ignores_case() {
+        assert_eq!(normalize_search_term("LemCom"), "lemcom");
+    }
+
+    #[test]
+    fn normalize_search_term_strips_accents() {
+        assert_eq!(normalize_search_term("José"), "jose");
+        assert_eq!(normalize_search_term("Zoë Ångström"), "zoe angstrom");
+    }
+
+    #[test]
+    fn normalize_search_term_folds_full_width_characters() {
+        assert_eq!(normalize_search_term("ＪＯＳＥ"), "jose");
+        assert_eq!(
+            normalize_search_term("ＪＯＳＥ"),
+            normalize_search_term("José")
+        );
+    }
+
+    #[test]
+    fn normalize_search_term_keeps_other_characters() {
+        assert_eq!(normalize_search_term("lem_com 42!"), "lem_com 42!");
+        assert_eq!(normalize_search_term(""), "");
+    }
+}
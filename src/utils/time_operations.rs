@@ -1,4 +1,4 @@
-use chrono::{offset::LocalResult, TimeZone};
+use chrono::{offset::LocalResult, DateTime, NaiveTime, TimeZone};
 use chrono_tz::{Tz, TZ_VARIANTS};
 use lazy_static::lazy_static;
 use std::{
@@ -37,6 +37,39 @@ pub fn nanos_to_date(nanos: u64, tz: &Tz) -> String {
     }
 }
 
+pub fn nanos_to_rfc3339(nanos: u64) -> String {
+    DateTime::from_timestamp_nanos(nanos as i64).to_rfc3339()
+}
+
+pub fn rfc3339_to_nanos(date: &str) -> Option<u64> {
+    let datetime = DateTime::parse_from_rfc3339(date).ok()?;
+    let nanos = datetime.timestamp_nanos_opt()?;
+    u64::try_from(nanos).ok()
+}
+
+/// Parses a time of day in the format HH:MM
+pub fn parse_time_of_day(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M").ok()
+}
+
+pub fn nanos_to_time_of_day(nanos: u64, tz: &Tz) -> Option<NaiveTime> {
+    let seconds = (nanos / 1_000_000_000) as i64;
+    match tz.timestamp_opt(seconds, 0) {
+        LocalResult::Single(datetime) => Some(datetime.time()),
+        _ => None,
+    }
+}
+
+/// Checks if the time lies within the daily window from start (inclusive) to end (exclusive).
+/// If the end is before the start, the window wraps around midnight.
+pub fn is_within_daily_window(start: NaiveTime, end: NaiveTime, time: NaiveTime) -> bool {
+    if start <= end {
+        start <= time && time < end
+    } else {
+        time >= start || time < end
+    }
+}
+
 pub fn get_timezone_names() -> Vec<String> {
     TIMEZONES.clone()
 }
@@ -53,3 +86,74 @@ pub fn get_timezone_with_default(name: &str) -> &Tz {
         None => &chrono_tz::UTC,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc3339_to_nanos_parses_dates() {
+        assert_eq!(
+            rfc3339_to_nanos("1970-01-01T00:00:01Z"),
+            Some(1_000_000_000)
+        );
+        assert_eq!(
+            rfc3339_to_nanos("2024-04-20T12:00:00.5+02:00"),
+            Some(1_713_607_200_500_000_000)
+        );
+    }
+
+    #[test]
+    fn rfc3339_to_nanos_roundtrips() {
+        let nanos = 1_713_607_200_123_456_789;
+        assert_eq!(rfc3339_to_nanos(&nanos_to_rfc3339(nanos)), Some(nanos));
+    }
+
+    #[test]
+    fn rfc3339_to_nanos_rejects_invalid_dates() {
+        assert_eq!(rfc3339_to_nanos("2024-04-20"), None);
+        assert_eq!(rfc3339_to_nanos("yesterday"), None);
+        assert_eq!(rfc3339_to_nanos("1969-12-31T23:59:59Z"), None);
+    }
+
+    #[test]
+    fn parse_time_of_day_requires_hours_and_minutes() {
+        assert_eq!(
+            parse_time_of_day("07:30"),
+            NaiveTime::from_hms_opt(7, 30, 0)
+        );
+        assert_eq!(parse_time_of_day("24:00"), None);
+        assert_eq!(parse_time_of_day("7"), None);
+    }
+
+    #[test]
+    fn nanos_to_time_of_day_uses_timezone() {
+        let nanos = 1_713_614_400_000_000_000; // 2024-04-20T12:00:00Z
+        assert_eq!(
+            nanos_to_time_of_day(nanos, &Tz::UTC),
+            NaiveTime::from_hms_opt(12, 0, 0)
+        );
+        assert_eq!(
+            nanos_to_time_of_day(nanos, &Tz::Europe__Berlin),
+            NaiveTime::from_hms_opt(14, 0, 0)
+        );
+    }
+
+    #[test]
+    fn daily_window_within_one_day() {
+        let time = |hour| NaiveTime::from_hms_opt(hour, 0, 0).unwrap();
+        assert!(is_within_daily_window(time(9), time(17), time(9)));
+        assert!(is_within_daily_window(time(9), time(17), time(12)));
+        assert!(!is_within_daily_window(time(9), time(17), time(17)));
+        assert!(!is_within_daily_window(time(9), time(17), time(8)));
+    }
+
+    #[test]
+    fn daily_window_spanning_midnight() {
+        let time = |hour| NaiveTime::from_hms_opt(hour, 0, 0).unwrap();
+        assert!(is_within_daily_window(time(22), time(7), time(23)));
+        assert!(is_within_daily_window(time(22), time(7), time(3)));
+        assert!(!is_within_daily_window(time(22), time(7), time(7)));
+        assert!(!is_within_daily_window(time(22), time(7), time(12)));
+    }
+}
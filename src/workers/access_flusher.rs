@@ -0,0 +1,44 @@
+use crate::api::entities::user::flush_access;
+use crate::api::workers::worker::Worker;
+use crate::config::Config;
+use crate::AppState;
+use axum::async_trait;
+use std::time::Duration;
+
+/// Periodically writes the buffered user accesses to the database.
+pub struct AccessFlusher;
+
+#[async_trait]
+impl Worker for AccessFlusher {
+    fn name(&self) -> &'static str {
+        "access flusher"
+    }
+
+    fn interval(&self, config: &Config) -> Duration {
+        Duration::from_secs(config.access_flush_interval_seconds)
+    }
+
+    async fn tick(&self, state: &AppState) {
+        flush(state).await;
+    }
+
+    /// Flushes one last time so no accesses are lost
+    async fn shutdown(&self, state: &AppState) {
+        flush(state).await;
+    }
+}
+
+/// Writes all buffered accesses at once
+async fn flush(state: &AppState) {
+    let pending = state.access_buffer.take();
+    if pending.is_empty() {
+        return;
+    }
+
+    let collection = &state.database.user_collection;
+    for (key, access) in pending {
+        if let Err(e) = flush_access(collection, &key, &access).await {
+            println!("Flushing the access of a user failed: {e}");
+        }
+    }
+}
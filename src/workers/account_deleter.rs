@@ -0,0 +1,62 @@
+use crate::api::entities::friendship::remove_friendships_by_key;
+use crate::api::entities::notification::remove_notifications_involving_key;
+use crate::api::entities::user::{
+    delete_user_by_key, find_users_due_for_deletion, remove_key_references,
+};
+use crate::api::utils::time_operations::timestamp_now_nanos;
+use crate::api::workers::worker::Worker;
+use crate::config::Config;
+use crate::AppState;
+use axum::async_trait;
+use std::time::Duration;
+
+const DELETION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Permanently deletes the accounts whose deletion grace period ended, together with everything referencing them.
+/// The account itself is deleted last, so an interrupted deletion is simply finished on the next tick.
+pub struct AccountDeleter;
+
+#[async_trait]
+impl Worker for AccountDeleter {
+    fn name(&self) -> &'static str {
+        "account deleter"
+    }
+
+    fn interval(&self, _config: &Config) -> Duration {
+        DELETION_INTERVAL
+    }
+
+    async fn tick(&self, state: &AppState) {
+        let users = match find_users_due_for_deletion(
+            &state.database.user_collection,
+            timestamp_now_nanos(),
+        )
+        .await
+        {
+            Ok(users) => users,
+            Err(e) => {
+                println!("Account deletion failed: {e}");
+                return;
+            }
+        };
+
+        let mut deleted = 0;
+        for user in users {
+            match delete_account(state, &user.key).await {
+                Ok(()) => deleted += 1,
+                Err(e) => println!("Account deletion of '{}' failed: {e}", user.name),
+            }
+        }
+        if deleted > 0 {
+            println!("Account deletion: deleted {deleted} account(s)");
+        }
+    }
+}
+
+async fn delete_account(state: &AppState, key: &str) -> mongodb::error::Result<()> {
+    remove_friendships_by_key(&state.database.friendship_collection, key).await?;
+    remove_notifications_involving_key(&state.database.notification_collection, key).await?;
+    remove_key_references(&state.database.user_collection, key).await?;
+    delete_user_by_key(&state.database.user_collection, key).await?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use crate::api::entities::user::{count_inactive_users, flag_inactive_users};
+use crate::api::utils::time_operations::timestamp_now_nanos;
+use crate::api::workers::worker::Worker;
+use crate::config::Config;
+use crate::AppState;
+use axum::async_trait;
+use std::time::Duration;
+
+const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// Returns the timestamp before which the last access of an account has to be for it to count as inactive
+pub fn inactive_cutoff_stamp(inactive_account_days: u64) -> u64 {
+    timestamp_now_nanos().saturating_sub(inactive_account_days * NANOS_PER_DAY)
+}
+
+/// Returns the timestamp at which an account counts as inactive if it doesn't send any further requests
+pub fn inactive_since_stamp(last_access_stamp: u64, inactive_account_days: u64) -> u64 {
+    last_access_stamp + inactive_account_days * NANOS_PER_DAY
+}
+
+/// Periodically looks for inactive accounts and flags them, never deletes any data.
+pub struct InactiveSweeper;
+
+#[async_trait]
+impl Worker for InactiveSweeper {
+    fn name(&self) -> &'static str {
+        "inactive account sweeper"
+    }
+
+    fn interval(&self, _config: &Config) -> Duration {
+        SWEEP_INTERVAL
+    }
+
+    async fn tick(&self, state: &AppState) {
+        sweep(state).await;
+    }
+}
+
+async fn sweep(state: &AppState) {
+    let cutoff = inactive_cutoff_stamp(state.config.inactive_account_days);
+    let collection = &state.database.user_collection;
+
+    if state.config.inactive_sweep_dry_run {
+        match count_inactive_users(collection, cutoff).await {
+            Ok(count) => println!("Inactive account sweep (dry run): {count} candidate(s)"),
+            Err(e) => println!("Inactive account sweep failed: {e}"),
+        }
+        return;
+    }
+
+    match flag_inactive_users(collection, cutoff).await {
+        Ok(count) => println!("Inactive account sweep: flagged {count} account(s)"),
+        Err(e) => println!("Inactive account sweep failed: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inactive_since_adds_inactive_period() {
+        assert_eq!(inactive_since_stamp(0, 30), 30 * NANOS_PER_DAY);
+        assert_eq!(inactive_since_stamp(1_000, 0), 1_000);
+    }
+
+    #[test]
+    fn inactive_cutoff_lies_inactive_period_in_the_past() {
+        let before = timestamp_now_nanos();
+        let cutoff = inactive_cutoff_stamp(30);
+        let after = timestamp_now_nanos();
+
+        assert!(cutoff >= before - 30 * NANOS_PER_DAY);
+        assert!(cutoff <= after - 30 * NANOS_PER_DAY);
+        assert!(inactive_since_stamp(cutoff, 30) <= after);
+    }
+}
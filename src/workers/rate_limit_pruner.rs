@@ -0,0 +1,23 @@
+use crate::api::workers::worker::Worker;
+use crate::config::Config;
+use crate::AppState;
+use axum::async_trait;
+use std::time::Duration;
+
+/// Periodically forgets the rate limit windows of IP addresses which ended.
+pub struct RateLimitPruner;
+
+#[async_trait]
+impl Worker for RateLimitPruner {
+    fn name(&self) -> &'static str {
+        "rate limit pruner"
+    }
+
+    fn interval(&self, config: &Config) -> Duration {
+        Duration::from_secs(config.ip_rate_limit_window_seconds)
+    }
+
+    async fn tick(&self, state: &AppState) {
+        state.ip_rate_limiter.prune();
+    }
+}
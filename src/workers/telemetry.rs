@@ -0,0 +1,52 @@
+use crate::api::entities::friendship::count_friendships;
+use crate::api::entities::user::{count_active_users, count_users};
+use crate::api::utils::time_operations::timestamp_now_nanos;
+use crate::api::workers::worker::Worker;
+use crate::config::Config;
+use crate::AppState;
+use axum::async_trait;
+use std::time::Duration;
+
+const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+/// Users who sent a request within this amount of days count as active
+const ACTIVE_USER_DAYS: u64 = 30;
+
+/// Aggregated counts only, never includes usernames, keys or any other user data
+struct TelemetryReport {
+    total_users: u64,
+    active_users: u64,
+    total_friendships: u64,
+}
+
+/// Periodically logs anonymized usage metrics for operators.
+pub struct Telemetry;
+
+#[async_trait]
+impl Worker for Telemetry {
+    fn name(&self) -> &'static str {
+        "telemetry"
+    }
+
+    fn interval(&self, config: &Config) -> Duration {
+        Duration::from_secs(config.telemetry_interval_hours * 60 * 60)
+    }
+
+    async fn tick(&self, state: &AppState) {
+        match collect(state).await {
+            Ok(report) => println!(
+                "Telemetry: total_users={} active_users={} total_friendships={}",
+                report.total_users, report.active_users, report.total_friendships
+            ),
+            Err(e) => println!("Telemetry collection failed: {e}"),
+        }
+    }
+}
+
+async fn collect(state: &AppState) -> mongodb::error::Result<TelemetryReport> {
+    let active_since = timestamp_now_nanos().saturating_sub(ACTIVE_USER_DAYS * NANOS_PER_DAY);
+    Ok(TelemetryReport {
+        total_users: count_users(&state.database.user_collection).await?,
+        active_users: count_active_users(&state.database.user_collection, active_since).await?,
+        total_friendships: count_friendships(&state.database.friendship_collection).await?,
+    })
+}
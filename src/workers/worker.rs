@@ -0,0 +1,166 @@
+use crate::config::Config;
+use crate::AppState;
+use axum::async_trait;
+use std::time::Duration;
+use tokio::{sync::watch, task::JoinHandle};
+
+/// A background task which runs periodically for as long as the server is running
+#[async_trait]
+pub trait Worker: Send + Sync + 'static {
+    /// The name used when logging about the worker
+    fn name(&self) -> &'static str;
+
+    /// How long to wait between two ticks, the first tick happens right on startup
+    fn interval(&self, config: &Config) -> Duration;
+
+    async fn tick(&self, state: &AppState);
+
+    /// Called once when the server shuts down, after the last tick finished
+    async fn shutdown(&self, _state: &AppState) {}
+}
+
+/// Collects the workers which should be started with the server
+#[derive(Default)]
+pub struct WorkerRegistry {
+    workers: Vec<Box<dyn Worker>>,
+}
+
+impl WorkerRegistry {
+    pub fn register(mut self, worker: impl Worker) -> Self {
+        self.workers.push(Box::new(worker));
+        self
+    }
+
+    pub fn start(self, state: AppState) -> RunningWorkers {
+        let (stop_sender, stop_receiver) = watch::channel(false);
+        let handles = self
+            .workers
+            .into_iter()
+            .map(|worker| tokio::spawn(run(worker, state.clone(), stop_receiver.clone())))
+            .collect();
+
+        RunningWorkers {
+            stop_sender,
+            handles,
+        }
+    }
+}
+
+/// The started workers, which keep running until they are shut down
+pub struct RunningWorkers {
+    stop_sender: watch::Sender<bool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl RunningWorkers {
+    /// Lets every worker finish its current tick and waits until all of them shut down
+    pub async fn shutdown(self) {
+        let _ = self.stop_sender.send(true);
+        for handle in self.handles {
+            if let Err(e) = handle.await {
+                println!("A worker stopped unexpectedly: {e}");
+            }
+        }
+    }
+}
+
+async fn run(worker: Box<dyn Worker>, state: AppState, mut stop_receiver: watch::Receiver<bool>) {
+    let mut interval = tokio::time::interval(worker.interval(state.config));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => worker.tick(&state).await,
+            _ = stop_receiver.changed() => break,
+        }
+    }
+    worker.shutdown(&state).await;
+    println!("Worker '{}' stopped", worker.name());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::database::db::DB;
+    use crate::api::events::event_hub::EventHub;
+    use crate::api::middleware::ip_rate_limit::IpRateLimiter;
+    use crate::api::utils::{access_buffer::AccessBuffer, cache::TimedCache};
+    use mongodb::Client;
+    use std::{
+        env,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    /// The client only connects once it is used, so workers which don't touch the database can run without one
+    async fn test_state() -> AppState {
+        env::set_var("DB_URL", "mongodb://localhost");
+        let config: &'static Config = Box::leak(Box::new(Config::from_env().unwrap()));
+        let client = Client::with_uri_str(&config.db_url).await.unwrap();
+        let db = client.database("LemComTest");
+
+        AppState {
+            access_buffer: AccessBuffer::default(),
+            config,
+            database: DB {
+                user_collection: db.collection("users"),
+                friendship_collection: db.collection("friendships"),
+                notification_collection: db.collection("notifications"),
+                audit_log_collection: db.collection("audit_log"),
+                client,
+            },
+            events: EventHub::default(),
+            ip_rate_limiter: IpRateLimiter::new(1, Duration::from_secs(1)),
+            recent_users_cache: TimedCache::new(Duration::from_secs(1)),
+            recent_presence_pings: TimedCache::new(Duration::from_secs(1)),
+            join_rank_cache: TimedCache::new(Duration::from_secs(1)),
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct CountingWorker {
+        ticks: Arc<AtomicUsize>,
+        shut_down: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl Worker for CountingWorker {
+        fn name(&self) -> &'static str {
+            "counting worker"
+        }
+
+        fn interval(&self, _config: &Config) -> Duration {
+            Duration::from_secs(3600)
+        }
+
+        async fn tick(&self, _state: &AppState) {
+            self.ticks.fetch_add(1, Ordering::SeqCst);
+        }
+
+        async fn shutdown(&self, _state: &AppState) {
+            self.shut_down.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn workers_tick_on_startup_and_shut_down() {
+        let first = CountingWorker::default();
+        let second = CountingWorker::default();
+        let workers = WorkerRegistry::default()
+            .register(first.clone())
+            .register(second.clone())
+            .start(test_state().await);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(first.ticks.load(Ordering::SeqCst), 1);
+        assert_eq!(second.ticks.load(Ordering::SeqCst), 1);
+        assert!(!first.shut_down.load(Ordering::SeqCst));
+
+        tokio::time::timeout(Duration::from_secs(1), workers.shutdown())
+            .await
+            .expect("Workers did not stop");
+        assert!(first.shut_down.load(Ordering::SeqCst));
+        assert!(second.shut_down.load(Ordering::SeqCst));
+        assert_eq!(first.ticks.load(Ordering::SeqCst), 1);
+    }
+}